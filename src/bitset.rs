@@ -0,0 +1,323 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitXor, Not, Sub};
+
+// Sets of small integers. `BitSet64` and `BitSet128` are `Copy` and hold
+// members in `0..64` and `0..128`; `BitSet` grows to fit any member.
+
+macro_rules! fixed_bitset {
+    ($name: ident, $subsets: ident, $word: ty) => {
+        #[derive(Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name($word);
+
+        impl $name {
+            pub const CAPACITY: usize = <$word>::BITS as usize;
+
+            pub const fn new() -> Self {
+                Self(0)
+            }
+
+            pub const fn from_bits(bits: $word) -> Self {
+                Self(bits)
+            }
+
+            pub const fn bits(self) -> $word {
+                self.0
+            }
+
+            /// The set `{0, 1, ..., n - 1}`.
+            pub fn full(n: usize) -> Self {
+                assert!(n <= Self::CAPACITY, "bitset member out of range");
+                Self(
+                    <$word>::MAX
+                        .checked_shr((Self::CAPACITY - n) as u32)
+                        .unwrap_or(0),
+                )
+            }
+
+            pub fn singleton(i: usize) -> Self {
+                let mut res = Self::new();
+                res.insert(i);
+                res
+            }
+
+            /// Adds `i` to the set, returning whether it was absent.
+            pub fn insert(&mut self, i: usize) -> bool {
+                assert!(i < Self::CAPACITY, "bitset member out of range");
+                let absent = !self.contains(i);
+                self.0 |= 1 << i;
+                absent
+            }
+
+            /// Removes `i` from the set, returning whether it was present.
+            pub fn remove(&mut self, i: usize) -> bool {
+                let present = self.contains(i);
+                if present {
+                    self.0 &= !(1 << i);
+                }
+                present
+            }
+
+            pub fn contains(self, i: usize) -> bool {
+                i < Self::CAPACITY && self.0 >> i & 1 == 1
+            }
+
+            pub fn len(self) -> usize {
+                self.0.count_ones() as usize
+            }
+
+            pub fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+
+            /// The smallest member.
+            pub fn first(self) -> Option<usize> {
+                (!self.is_empty()).then(|| self.0.trailing_zeros() as usize)
+            }
+
+            pub fn union(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+
+            pub fn intersection(self, other: Self) -> Self {
+                Self(self.0 & other.0)
+            }
+
+            pub fn difference(self, other: Self) -> Self {
+                Self(self.0 & !other.0)
+            }
+
+            pub fn symmetric_difference(self, other: Self) -> Self {
+                Self(self.0 ^ other.0)
+            }
+
+            pub fn is_subset(self, other: Self) -> bool {
+                self.0 & !other.0 == 0
+            }
+
+            pub fn is_disjoint(self, other: Self) -> bool {
+                self.0 & other.0 == 0
+            }
+
+            /// Members in increasing order.
+            pub fn iter(self) -> impl Iterator<Item = usize> {
+                let mut bits = self.0;
+                std::iter::from_fn(move || {
+                    if bits == 0 {
+                        return None;
+                    }
+                    let i = bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+                    Some(i)
+                })
+            }
+
+            /// Every subset of `self`, from the empty set up to `self`.
+            pub fn subsets(self) -> $subsets {
+                $subsets {
+                    mask: self.0,
+                    next: Some(0),
+                }
+            }
+        }
+
+        pub struct $subsets {
+            mask: $word,
+            next: Option<$word>,
+        }
+
+        impl Iterator for $subsets {
+            type Item = $name;
+
+            fn next(&mut self) -> Option<$name> {
+                let cur = self.next?;
+                let next = cur.wrapping_sub(self.mask) & self.mask;
+                self.next = (next != 0).then_some(next);
+                Some($name(cur))
+            }
+        }
+
+        impl FromIterator<usize> for $name {
+            fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+                let mut res = Self::new();
+                for i in iter {
+                    res.insert(i);
+                }
+                res
+            }
+        }
+
+        impl BitOr for $name {
+            type Output = Self;
+            fn bitor(self, other: Self) -> Self {
+                self.union(other)
+            }
+        }
+
+        impl BitAnd for $name {
+            type Output = Self;
+            fn bitand(self, other: Self) -> Self {
+                self.intersection(other)
+            }
+        }
+
+        impl BitXor for $name {
+            type Output = Self;
+            fn bitxor(self, other: Self) -> Self {
+                self.symmetric_difference(other)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+            fn sub(self, other: Self) -> Self {
+                self.difference(other)
+            }
+        }
+
+        impl Not for $name {
+            type Output = Self;
+            fn not(self) -> Self {
+                Self(!self.0)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_set().entries(self.iter()).finish()
+            }
+        }
+    };
+}
+
+fixed_bitset!(BitSet64, Subsets64, u64);
+fixed_bitset!(BitSet128, Subsets128, u128);
+
+#[derive(Clone, Default)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty set with room for members in `0..n` without reallocating.
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            words: vec![0; n.div_ceil(64)],
+        }
+    }
+
+    /// Adds `i` to the set, returning whether it was absent.
+    pub fn insert(&mut self, i: usize) -> bool {
+        let (w, b) = (i / 64, i % 64);
+        if w >= self.words.len() {
+            self.words.resize(w + 1, 0);
+        }
+        let absent = self.words[w] >> b & 1 == 0;
+        self.words[w] |= 1 << b;
+        absent
+    }
+
+    /// Removes `i` from the set, returning whether it was present.
+    pub fn remove(&mut self, i: usize) -> bool {
+        let present = self.contains(i);
+        if present {
+            self.words[i / 64] &= !(1 << (i % 64));
+        }
+        present
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.words
+            .get(i / 64)
+            .is_some_and(|w| w >> (i % 64) & 1 == 1)
+    }
+
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    pub fn union_with(&mut self, other: &BitSet) {
+        if self.words.len() < other.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (x, y) in self.words.iter_mut().zip(&other.words) {
+            *x |= y;
+        }
+    }
+
+    pub fn intersect_with(&mut self, other: &BitSet) {
+        self.words.truncate(other.words.len());
+        for (x, y) in self.words.iter_mut().zip(&other.words) {
+            *x &= y;
+        }
+    }
+
+    pub fn difference_with(&mut self, other: &BitSet) {
+        for (x, y) in self.words.iter_mut().zip(&other.words) {
+            *x &= !y;
+        }
+    }
+
+    pub fn symmetric_difference_with(&mut self, other: &BitSet) {
+        if self.words.len() < other.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (x, y) in self.words.iter_mut().zip(&other.words) {
+            *x ^= y;
+        }
+    }
+
+    pub fn is_subset(&self, other: &BitSet) -> bool {
+        self.words.iter().enumerate().all(|(i, &x)| {
+            let y = other.words.get(i).copied().unwrap_or(0);
+            x & !y == 0
+        })
+    }
+
+    pub fn is_disjoint(&self, other: &BitSet) -> bool {
+        self.words.iter().zip(&other.words).all(|(x, y)| x & y == 0)
+    }
+
+    /// Members in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(w, &bits)| BitSet64::from_bits(bits).iter().map(move |b| 64 * w + b))
+    }
+}
+
+impl PartialEq for BitSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.is_subset(other) && other.is_subset(self)
+    }
+}
+
+impl Eq for BitSet {}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut res = Self::new();
+        for i in iter {
+            res.insert(i);
+        }
+        res
+    }
+}
+
+impl fmt::Debug for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
@@ -1,28 +1,231 @@
+use crate::parsing::value;
 use crate::{Aoc, Day01, Display, FileRep, Result};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap as Heap;
+use std::fmt;
+use std::io::BufRead;
+
+impl Default for Day01 {
+    fn default() -> Self {
+        Day01 {
+            top: 3,
+            report: false,
+        }
+    }
+}
 
 impl Aoc for Day01 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
-        result!(parse(lines)?.into_iter().max().unwrap())
+        let elves = top_k(totals(lines.iter().map(Ok)), 1)?;
+        result!(elves.into_iter().sum::<u32>())
     }
     fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
-        let mut elves: Vec<u32> = parse(lines)?;
-        elves.sort_unstable();
-        result!(elves.into_iter().rev().take(3).sum::<u32>())
-    }
-}
-
-fn parse(lines: &[&[u8]]) -> Result<Vec<u32>> {
-    lines
-        .split(|&l| l.is_empty())
-        .map(|ll| {
-            ll.iter()
-                .map::<Result<u32>, _>(|l| {
-                    let s = std::str::from_utf8(l)?;
-                    Ok(s.parse()?)
-                })
-                .sum()
+        let elves = top_k(totals(lines.iter().map(Ok)), self.top)?;
+        result!(elves.into_iter().sum::<u32>())
+    }
+
+    fn report(&self, input: &FileRep) -> Result<Option<Box<dyn Display>>> {
+        if !self.report {
+            return Ok(None);
+        }
+        let lines = &input.byte_lines;
+        Ok(Some(Box::new(Report {
+            elves: inventory(lines)?,
+            top: self.top,
+        })))
+    }
+}
+
+impl Day01 {
+    /// The `self.top` largest elf totals, largest first, read line by line
+    /// from `reader` without keeping the input in memory.
+    pub fn top_from_reader<R: BufRead>(&self, reader: R) -> Result<Vec<u32>> {
+        let lines = reader.split(b'\n').map(|l| Ok(l?));
+        top_k(totals(lines), self.top)
+    }
+}
+
+// the sum of each block of lines, blocks being separated by blank lines
+fn totals<I, L>(lines: I) -> impl Iterator<Item = Result<u32>>
+where
+    I: IntoIterator<Item = Result<L>>,
+    L: AsRef<[u8]>,
+{
+    elves(lines).map(|items| Ok(items?.into_iter().sum()))
+}
+
+// the items of each elf, one per line; `\r` line endings are accepted and
+// runs of blank lines separate just two elves
+fn elves<I, L>(lines: I) -> impl Iterator<Item = Result<Vec<u32>>>
+where
+    I: IntoIterator<Item = Result<L>>,
+    L: AsRef<[u8]>,
+{
+    let mut lines = lines.into_iter();
+    std::iter::from_fn(move || {
+        let mut items = vec![];
+        for line in lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            let line = line.as_ref().strip_suffix(b"\r").unwrap_or(line.as_ref());
+            if line.is_empty() {
+                if !items.is_empty() {
+                    break;
+                }
+                continue;
+            }
+            match value::<u32, _>(line) {
+                Ok(x) => items.push(x),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        (!items.is_empty()).then_some(Ok(items))
+    })
+}
+
+// the k largest items, largest first, keeping at most k + 1 of them around
+fn top_k<T: Ord>(items: impl IntoIterator<Item = Result<T>>, k: usize) -> Result<Vec<T>> {
+    let mut heap = Heap::new();
+    for x in items {
+        heap.push(Reverse(x?));
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+    Ok(heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(x)| x)
+        .collect())
+}
+
+struct Elf {
+    index: usize,
+    items: usize,
+    total: u32,
+    max_item: u32,
+}
+
+fn inventory(lines: &[&[u8]]) -> Result<Vec<Elf>> {
+    elves(lines.iter().map(Ok))
+        .enumerate()
+        .map(|(index, items)| {
+            let items = items?;
+            Ok(Elf {
+                index,
+                items: items.len(),
+                total: items.iter().sum(),
+                max_item: items.iter().copied().max().unwrap_or(0),
+            })
         })
         .collect()
 }
+
+struct Report {
+    elves: Vec<Elf>,
+    top: usize,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut totals: Vec<u32> = self.elves.iter().map(|e| e.total).collect();
+        totals.sort_unstable();
+        let n = totals.len();
+        if n == 0 {
+            return write!(f, "no elves");
+        }
+
+        let items: usize = self.elves.iter().map(|e| e.items).sum();
+        let mean = totals.iter().map(|&t| t as f64).sum::<f64>() / n as f64;
+        let median = if n % 2 == 1 {
+            totals[n / 2] as f64
+        } else {
+            (totals[n / 2 - 1] + totals[n / 2]) as f64 / 2.0
+        };
+        // nearest-rank percentile
+        let percentile = |p: usize| totals[(p * n).div_ceil(100).max(1) - 1];
+
+        writeln!(f)?;
+        writeln!(f, "{n} elves carrying {items} items")?;
+        writeln!(
+            f,
+            "calories per elf: min {}, mean {mean:.1}, median {median}, max {}",
+            totals[0],
+            totals[n - 1]
+        )?;
+        let percentiles = [10, 25, 50, 75, 90, 99]
+            .map(|p| format!("p{p} {}", percentile(p)))
+            .join(", ");
+        writeln!(f, "percentiles: {percentiles}")?;
+
+        writeln!(f, "top {}:", self.top)?;
+        let mut ranked: Vec<&Elf> = self.elves.iter().collect();
+        ranked.sort_by_key(|e| (Reverse(e.total), e.index));
+        for e in ranked.iter().take(self.top) {
+            writeln!(
+                f,
+                "  elf #{}: {} calories in {} items, largest {}",
+                e.index + 1,
+                e.total,
+                e.items,
+                e.max_item
+            )?;
+        }
+
+        writeln!(f, "histogram:")?;
+        let (lo, hi) = (totals[0], totals[n - 1]);
+        let nbuckets = 10;
+        let width = (hi - lo) / nbuckets + 1;
+        let mut buckets = vec![0_usize; nbuckets as usize];
+        for &t in &totals {
+            buckets[((t - lo) / width) as usize] += 1;
+        }
+        let largest = buckets.iter().copied().max().unwrap_or(1);
+        for (i, &count) in buckets.iter().enumerate() {
+            let start = lo + i as u32 * width;
+            let bar = "#".repeat((count * 40).div_ceil(largest));
+            write!(
+                f,
+                "  {:>7} - {:>7} | {bar} {count}",
+                start,
+                start + width - 1
+            )?;
+            if i + 1 < buckets.len() {
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(input: &str) -> Vec<&[u8]> {
+        input.split('\n').map(str::as_bytes).collect()
+    }
+
+    #[test]
+    fn inventory_matches_totals() {
+        for input in ["1\n\n\n2", "1\r\n2\r\n\r\n3\r\n", "\n\n4\n5\n\n\n\n6\n\n"] {
+            let lines = lines(input);
+            let elves = inventory(&lines).unwrap();
+            let totals: Vec<u32> = totals(lines.iter().map(Ok)).collect::<Result<_>>().unwrap();
+            assert_eq!(elves.iter().map(|e| e.total).collect::<Vec<_>>(), totals);
+            assert!(elves.iter().enumerate().all(|(i, e)| e.index == i));
+        }
+        assert_eq!(inventory(&lines("1\n\n\n2")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn top_k_beyond_the_number_of_elves() {
+        let totals = [3, 1, 2].map(Ok);
+        assert_eq!(top_k(totals, usize::MAX).unwrap(), [3, 2, 1]);
+    }
+}
@@ -1,96 +1,348 @@
-use crate::{bail, eyre, Aoc, Day02, Display, FileRep, Result};
+use crate::bitset::BitSet;
+use crate::parsing::{parse_lines, scan};
+use crate::{bail, ensure, eyre, Aoc, Day02, Display, FileRep, Result};
+use std::fmt;
+use tracing::instrument;
 
 impl Aoc for Day02 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
-        result!(score(parse_part1(lines)?))
+        result!(self.game.score(&parse_part1(&self.game, lines)?))
     }
     fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
-        result!(score(parse_part2(lines)?))
+        result!(self.game.score(&parse_part2(&self.game, lines)?))
     }
-}
 
-#[derive(Clone, Copy)]
-enum Move {
-    Rock,
-    Paper,
-    Scissor,
+    fn report(&self, input: &FileRep) -> Result<Option<Box<dyn Display>>> {
+        let Some(target) = self.target else {
+            return Ok(None);
+        };
+        let lines = &input.byte_lines;
+        let opponent = parse_opponent(&self.game, lines)?;
+        Ok(Some(Box::new(self.game.plan(&opponent, target))))
+    }
 }
 
-fn score(games: Vec<(Move, Move)>) -> u32 {
-    let mut score = 0;
-    for g in games {
-        score += match g {
-            (_, Move::Rock) => 1,
-            (_, Move::Paper) => 2,
-            (_, Move::Scissor) => 3,
-        };
-        score += match g {
-            (Move::Rock, Move::Rock)
-            | (Move::Paper, Move::Paper)
-            | (Move::Scissor, Move::Scissor) => 3,
-            (Move::Rock, Move::Paper)
-            | (Move::Paper, Move::Scissor)
-            | (Move::Scissor, Move::Rock) => 6,
-            _ => 0,
-        };
+impl Default for Day02 {
+    fn default() -> Self {
+        Day02 {
+            game: Game::rock_paper_scissors(),
+            target: None,
+        }
     }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Outcome {
+    Loss,
+    Draw,
+    Win,
+}
 
-    score
+/// A game where both players pick one of n moves at once. Moves are
+/// indices into the rules table.
+#[derive(Clone, Debug)]
+pub struct Game {
+    /// Letter of each move in the opponent's column of a strategy guide.
+    them: Vec<u8>,
+    /// Letter of each move in our column of a strategy guide.
+    me: Vec<u8>,
+    shape_scores: Vec<u32>,
+    /// Scores for a loss, a draw and a win.
+    outcome_scores: [u32; 3],
+    /// `beats[i][j]` is whether move `i` beats move `j`.
+    beats: Vec<Vec<bool>>,
 }
 
-fn parse_part1(lines: &[&[u8]]) -> Result<Vec<(Move, Move)>> {
-    let mut v = Vec::new();
-    for l in lines {
-        let them = parse_move(b'A', *l.first().ok_or_else(|| eyre!("parse error"))?)?;
-        let me = parse_move(b'X', *l.get(2).ok_or_else(|| eyre!("parse error"))?)?;
+impl Game {
+    /// A game given by its full rules table, which must decide every pair
+    /// of distinct moves one way.
+    pub fn new(
+        them: &[u8],
+        me: &[u8],
+        shape_scores: &[u32],
+        outcome_scores: [u32; 3],
+        beats: Vec<Vec<bool>>,
+    ) -> Result<Game> {
+        let n = beats.len();
+        ensure!(n > 0, "a game needs at least one move");
+        ensure!(
+            them.len() == n && me.len() == n && shape_scores.len() == n,
+            "expected {n} letters and scores per player"
+        );
+        for letters in [them, me] {
+            ensure!(
+                (1..n).all(|i| !letters[..i].contains(&letters[i])),
+                "duplicate move letter in {:?}",
+                String::from_utf8_lossy(letters)
+            );
+        }
+        for (i, row) in beats.iter().enumerate() {
+            ensure!(row.len() == n, "rules table is not square");
+            for j in 0..n {
+                ensure!(
+                    i == j || row[j] != beats[j][i],
+                    "rules do not decide between moves {i} and {j}"
+                );
+            }
+            ensure!(!row[i], "move {i} beats itself");
+        }
+
+        Ok(Game {
+            them: them.to_vec(),
+            me: me.to_vec(),
+            shape_scores: shape_scores.to_vec(),
+            outcome_scores,
+            beats,
+        })
+    }
+
+    /// A balanced game of an odd number of moves listed in cyclic order,
+    /// where each move beats the moves an odd number of steps before it.
+    pub fn cyclic(
+        them: &[u8],
+        me: &[u8],
+        shape_scores: &[u32],
+        outcome_scores: [u32; 3],
+    ) -> Result<Game> {
+        let n = them.len();
+        ensure!(n % 2 == 1, "a cyclic game needs an odd number of moves");
+        let beats = (0..n)
+            .map(|i| (0..n).map(|j| (n + i - j) % n % 2 == 1).collect())
+            .collect();
+        Game::new(them, me, shape_scores, outcome_scores, beats)
+    }
+
+    pub fn rock_paper_scissors() -> Game {
+        Game::cyclic(b"ABC", b"XYZ", &[1, 2, 3], [0, 3, 6]).unwrap()
+    }
+
+    /// Moves are Rock, Paper, Scissors, Spock and Lizard, in that order.
+    pub fn rock_paper_scissors_lizard_spock() -> Game {
+        Game::cyclic(b"ABCDE", b"VWXYZ", &[1, 2, 3, 4, 5], [0, 3, 6]).unwrap()
+    }
+
+    fn outcome(&self, them: usize, me: usize) -> Outcome {
+        if me == them {
+            Outcome::Draw
+        } else if self.beats[me][them] {
+            Outcome::Win
+        } else {
+            Outcome::Loss
+        }
+    }
+
+    fn round_score(&self, them: usize, me: usize) -> u32 {
+        self.shape_scores[me] + self.outcome_scores[self.outcome(them, me) as usize]
+    }
+
+    fn score(&self, games: &[(usize, usize)]) -> u32 {
+        games
+            .iter()
+            .map(|&(them, me)| self.round_score(them, me))
+            .sum()
+    }
+
+    // Finds our moves against `opponent` that add up to exactly `target`,
+    // keeping for each round the set of totals reachable so far.
+    fn plan(&self, opponent: &[usize], target: u32) -> Plan {
+        let n = self.me.len();
+        let round = |them: usize| (0..n).map(move |me| (me, self.round_score(them, me)));
+        let min = opponent
+            .iter()
+            .map(|&t| round(t).map(|r| r.1).min().unwrap_or(0))
+            .sum();
+        let max = opponent
+            .iter()
+            .map(|&t| round(t).map(|r| r.1).max().unwrap_or(0))
+            .sum();
 
-        v.push((them, me));
+        let mut reachable = vec![BitSet::from_iter([0])];
+        if (min..=max).contains(&target) {
+            for &them in opponent {
+                let prev = reachable.last().unwrap();
+                let mut next = BitSet::with_capacity(target as usize + 1);
+                for total in prev.iter() {
+                    for (_, s) in round(them) {
+                        if total + s as usize <= target as usize {
+                            next.insert(total + s as usize);
+                        }
+                    }
+                }
+                reachable.push(next);
+            }
+        }
+
+        let mut witness = None;
+        if reachable.len() == opponent.len() + 1
+            && reachable[opponent.len()].contains(target as usize)
+        {
+            let mut total = target as usize;
+            let mut moves = vec![];
+            for (i, &them) in opponent.iter().enumerate().rev() {
+                let (me, s) = round(them)
+                    .find(|&(_, s)| {
+                        total >= s as usize && reachable[i].contains(total - s as usize)
+                    })
+                    .unwrap();
+                moves.push(self.me[me]);
+                total -= s as usize;
+            }
+            moves.reverse();
+            witness = Some(String::from_utf8_lossy(&moves).into_owned());
+        }
+
+        Plan {
+            target,
+            min,
+            max,
+            witness,
+        }
     }
 
-    Ok(v)
+    // the best scoring move giving the wanted outcome
+    fn response(&self, them: usize, outcome: Outcome) -> Option<usize> {
+        (0..self.me.len())
+            .filter(|&me| self.outcome(them, me) == outcome)
+            .max_by_key(|&me| self.shape_scores[me])
+    }
 }
 
-fn beats(m: Move) -> Move {
-    match m {
-        Move::Rock => Move::Paper,
-        Move::Paper => Move::Scissor,
-        Move::Scissor => Move::Rock,
+struct Plan {
+    target: u32,
+    min: u32,
+    max: u32,
+    /// Our letter for each round, when the target can be reached.
+    witness: Option<String>,
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "scores from {} to {}; ", self.min, self.max)?;
+        match &self.witness {
+            Some(moves) => write!(f, "{} is reached by playing {moves}", self.target),
+            None => write!(f, "{} cannot be reached", self.target),
+        }
     }
 }
 
-fn loses(m: Move) -> Move {
-    match m {
-        Move::Rock => Move::Scissor,
-        Move::Paper => Move::Rock,
-        Move::Scissor => Move::Paper,
+fn parse_move(letters: &[u8], m: &str) -> Result<usize> {
+    match m.as_bytes() {
+        [x] => letters.iter().position(|l| l == x),
+        _ => None,
     }
+    .ok_or_else(|| eyre!("parse error: invalid move {m:?}"))
 }
 
-fn parse_move(basis: u8, m: u8) -> Result<Move> {
-    let res = match m {
-        x if x == basis => Move::Rock,
-        x if x == basis + 1 => Move::Paper,
-        x if x == basis + 2 => Move::Scissor,
-        _ => bail!("parse error"),
-    };
-    Ok(res)
+#[instrument(level = "debug", skip_all)]
+fn parse_part1(game: &Game, lines: &[&[u8]]) -> Result<Vec<(usize, usize)>> {
+    parse_lines(lines, |l| {
+        let [them, me] = scan(l, "{} {}")?;
+        Ok((parse_move(&game.them, them)?, parse_move(&game.me, me)?))
+    })
 }
 
-fn parse_part2(lines: &[&[u8]]) -> Result<Vec<(Move, Move)>> {
-    let mut v = Vec::new();
-    for l in lines {
-        let them = parse_move(b'A', *l.first().ok_or_else(|| eyre!("parse error"))?)?;
-        let me = match l.get(2).ok_or_else(|| eyre!("parse error"))? {
-            b'X' => loses(them),
-            b'Y' => them,
-            b'Z' => beats(them),
-            _ => bail!("parse error"),
+#[instrument(level = "debug", skip_all)]
+fn parse_part2(game: &Game, lines: &[&[u8]]) -> Result<Vec<(usize, usize)>> {
+    parse_lines(lines, |l| {
+        let [them, outcome] = scan(l, "{} {}")?;
+        let them = parse_move(&game.them, them)?;
+        let outcome = match outcome {
+            "X" => Outcome::Loss,
+            "Y" => Outcome::Draw,
+            "Z" => Outcome::Win,
+            _ => bail!("parse error: invalid outcome {outcome:?}"),
+        };
+        let Some(me) = game.response(them, outcome) else {
+            bail!("no move gives a {outcome:?} against move {them}");
         };
+        Ok((them, me))
+    })
+}
 
-        v.push((them, me));
+// the opponent's column of a strategy guide, ignoring anything after it
+#[instrument(level = "debug", skip_all)]
+fn parse_opponent(game: &Game, lines: &[&[u8]]) -> Result<Vec<usize>> {
+    parse_lines(lines, |l| {
+        let them = l.split(|&c| c == b' ').next().unwrap_or_default();
+        parse_move(&game.them, std::str::from_utf8(them)?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROCK: usize = 0;
+    const PAPER: usize = 1;
+    const SCISSORS: usize = 2;
+    const SPOCK: usize = 3;
+    const LIZARD: usize = 4;
+
+    #[test]
+    fn lizard_spock_rules() {
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let wins = [
+            (SCISSORS, PAPER),
+            (PAPER, ROCK),
+            (ROCK, LIZARD),
+            (LIZARD, SPOCK),
+            (SPOCK, SCISSORS),
+            (SCISSORS, LIZARD),
+            (LIZARD, PAPER),
+            (PAPER, SPOCK),
+            (SPOCK, ROCK),
+            (ROCK, SCISSORS),
+        ];
+        for i in 0..5 {
+            for j in 0..5 {
+                assert_eq!(game.beats[i][j], wins.contains(&(i, j)), "{i} against {j}");
+            }
+        }
+
+        // Spock against Scissors, Lizard against Rock, Paper against Paper
+        let lines: [&[u8]; 3] = [b"C Y", b"A Z", b"B W"];
+        let rounds = parse_part1(&game, &lines).unwrap();
+        assert_eq!(rounds, [(SCISSORS, SPOCK), (ROCK, LIZARD), (PAPER, PAPER)]);
+        assert_eq!(game.score(&rounds), (4 + 6) + 5 + (2 + 3));
+    }
+
+    // the score of playing the witness of `plan` against `opponent`
+    fn witness_score(game: &Game, opponent: &[usize], plan: &Plan) -> u32 {
+        let moves = plan.witness.as_ref().unwrap().bytes();
+        let rounds: Vec<_> = opponent
+            .iter()
+            .zip(moves)
+            .map(|(&them, me)| (them, game.me.iter().position(|&l| l == me).unwrap()))
+            .collect();
+        assert_eq!(rounds.len(), opponent.len());
+        game.score(&rounds)
+    }
+
+    #[test]
+    fn reachable_targets() {
+        let game = Game::rock_paper_scissors();
+        let opponent = [ROCK, PAPER, SCISSORS];
+        for target in [6, 15, 24] {
+            let plan = game.plan(&opponent, target);
+            assert_eq!((plan.min, plan.max), (6, 24));
+            assert_eq!(witness_score(&game, &opponent, &plan), target);
+        }
     }
 
-    Ok(v)
+    #[test]
+    fn unreachable_targets() {
+        let game = Game::rock_paper_scissors();
+        let plan = game.plan(&[ROCK, PAPER, SCISSORS], 25);
+        assert_eq!((plan.min, plan.max, &plan.witness), (6, 24, &None));
+        assert_eq!(
+            plan.to_string(),
+            "scores from 6 to 24; 25 cannot be reached"
+        );
+
+        // against Rock a round scores 3, 4 or 8
+        let plan = game.plan(&[ROCK], 5);
+        assert_eq!((plan.min, plan.max, plan.witness), (3, 8, None));
+    }
 }
@@ -1,54 +1,242 @@
-use crate::{Aoc, Day03, Display, FileRep, Result};
+use crate::bitset::BitSet64;
+use crate::parsing::parse_lines;
+use crate::{bail, ensure, Aoc, Day03, Display, FileRep, Result};
+use std::fmt;
+use tracing::debug;
+
+impl Default for Day03 {
+    fn default() -> Self {
+        Day03 {
+            compartments: 2,
+            group_size: 3,
+            report: false,
+        }
+    }
+}
 
 impl Aoc for Day03 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
-        let mut res = 0;
-        for l in lines {
-            let mut count: Vec<u32> = vec![0; 128];
-            let (left, right) = l.split_at(l.len() / 2);
-            for x in left {
-                count[*x as usize] += 1;
-            }
-            for y in right {
-                if count[*y as usize] > 0 {
-                    let priority = if *y < b'a' {
-                        *y - b'A' + 27
-                    } else {
-                        *y - b'a' + 1
-                    };
-                    res += priority as u32;
-                    break;
-                }
-            }
+        let shared = parse_lines(lines, |l| Ok(common(compartments(l, self.compartments)?)))?;
+        result!(shared.into_iter().map(priority_sum).sum::<u32>())
+    }
+
+    fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
+        let lines = &input.byte_lines;
+        if self.group_size == 0 || !lines.len().is_multiple_of(self.group_size) {
+            bail!(
+                "{} rucksacks can't be split into groups of {}",
+                lines.len(),
+                self.group_size
+            );
         }
+        let rucksacks = parse_lines(lines, |l| items(l))?;
+        let res = rucksacks
+            .chunks(self.group_size)
+            .map(|group| priority_sum(common(group.iter().copied())))
+            .sum::<u32>();
         result!(res)
     }
 
-    fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
+    fn report(&self, input: &FileRep) -> Result<Option<Box<dyn Display>>> {
+        if !self.report {
+            return Ok(None);
+        }
         let lines = &input.byte_lines;
-        let mut res = 0;
-        for ll in lines.chunks(3) {
-            let mut counts: Vec<Vec<u32>> = Vec::new();
-            for &l in ll {
-                let mut count = vec![0; 128];
-                for x in l {
-                    count[*x as usize] += 1;
-                }
-                counts.push(count);
+        let rucksacks = parse_lines(lines, |l| items(l))?;
+        let groups = assign_badges(&rucksacks, self.group_size);
+        Ok(Some(Box::new(Badges(groups))))
+    }
+}
+
+// items are stored by priority: a-z are 1-26, A-Z are 27-52
+fn items(rucksack: &[u8]) -> Result<BitSet64> {
+    rucksack
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| match x {
+            b'a'..=b'z' => Ok((x - b'a' + 1) as usize),
+            b'A'..=b'Z' => Ok((x - b'A' + 27) as usize),
+            _ => bail!("invalid item {:?} at column {}", x as char, i + 1),
+        })
+        .collect()
+}
+
+fn compartments(rucksack: &[u8], n: usize) -> Result<Vec<BitSet64>> {
+    if n == 0 || !rucksack.len().is_multiple_of(n) {
+        bail!(
+            "a rucksack of {} items can't be split into {n} compartments",
+            rucksack.len()
+        );
+    }
+    ensure!(!rucksack.is_empty(), "empty rucksack");
+    rucksack.chunks(rucksack.len() / n).map(items).collect()
+}
+
+// the items found in every one of the sets
+fn common(sets: impl IntoIterator<Item = BitSet64>) -> BitSet64 {
+    sets.into_iter()
+        .fold(BitSet64::full(53) - BitSet64::singleton(0), |acc, s| {
+            acc & s
+        })
+}
+
+fn priority_sum(items: BitSet64) -> u32 {
+    items.iter().map(|p| p as u32).sum()
+}
+
+// a group of rucksacks, by index, and its badge
+type Group = (Vec<usize>, usize);
+
+// Splits rucksacks taken in any order into groups of k sharing exactly one
+// item. This is an exact cover problem: all candidate groups are listed,
+// then the search always continues from the rucksack left with the fewest
+// candidate groups.
+fn assign_badges(rucksacks: &[BitSet64], k: usize) -> Option<Vec<Group>> {
+    fn candidates(
+        rucksacks: &[BitSet64],
+        k: usize,
+        group: &mut Vec<usize>,
+        common: BitSet64,
+        res: &mut Vec<Group>,
+    ) {
+        if group.len() == k {
+            if common.len() == 1 {
+                res.push((group.clone(), common.first().unwrap()));
+            }
+            return;
+        }
+        let start = group.last().map_or(0, |&i| i + 1);
+        for j in start..rucksacks.len() {
+            let common = common & rucksacks[j];
+            if !common.is_empty() {
+                group.push(j);
+                candidates(rucksacks, k, group, common, res);
+                group.pop();
             }
-            for i in 0..128_u8 {
-                if counts.iter().all(|c| c[i as usize] > 0) {
-                    let priority = if i < b'a' {
-                        i - b'A' + 27
-                    } else {
-                        i - b'a' + 1
-                    };
-                    res += priority as u32;
-                    break;
+        }
+    }
+
+    struct Search {
+        groups: Vec<Group>,
+        // candidate groups of each rucksack
+        groups_of: Vec<Vec<usize>>,
+        active: Vec<bool>,
+        // number of active candidate groups of each rucksack
+        count: Vec<usize>,
+        assigned: Vec<bool>,
+        chosen: Vec<usize>,
+        nodes: usize,
+    }
+
+    impl Search {
+        fn run(&mut self) -> bool {
+            self.nodes += 1;
+            let Some(r) = (0..self.assigned.len())
+                .filter(|&r| !self.assigned[r])
+                .min_by_key(|&r| self.count[r])
+            else {
+                return true;
+            };
+
+            let options: Vec<usize> = self.groups_of[r]
+                .iter()
+                .copied()
+                .filter(|&g| self.active[g])
+                .collect();
+            for g in options {
+                let mut removed = vec![];
+                for &m in &self.groups[g].0 {
+                    self.assigned[m] = true;
+                    for &h in &self.groups_of[m] {
+                        if self.active[h] {
+                            self.active[h] = false;
+                            for &x in &self.groups[h].0 {
+                                self.count[x] -= 1;
+                            }
+                            removed.push(h);
+                        }
+                    }
+                }
+                self.chosen.push(g);
+
+                if self.run() {
+                    return true;
+                }
+
+                self.chosen.pop();
+                for h in removed {
+                    self.active[h] = true;
+                    for &x in &self.groups[h].0 {
+                        self.count[x] += 1;
+                    }
+                }
+                for &m in &self.groups[g].0 {
+                    self.assigned[m] = false;
                 }
             }
+            false
         }
-        result!(res)
+    }
+
+    if k == 0 || !rucksacks.len().is_multiple_of(k) {
+        return None;
+    }
+
+    let mut groups = vec![];
+    candidates(rucksacks, k, &mut vec![], BitSet64::full(64), &mut groups);
+    let mut groups_of = vec![vec![]; rucksacks.len()];
+    for (g, (members, _)) in groups.iter().enumerate() {
+        for &m in members {
+            groups_of[m].push(g);
+        }
+    }
+
+    let mut search = Search {
+        active: vec![true; groups.len()],
+        count: groups_of.iter().map(|gs| gs.len()).collect(),
+        assigned: vec![false; rucksacks.len()],
+        chosen: vec![],
+        nodes: 0,
+        groups,
+        groups_of,
+    };
+    let found = search.run();
+    debug!(
+        candidates = search.groups.len(),
+        nodes = search.nodes,
+        found,
+        "badge search"
+    );
+    found.then(|| {
+        search
+            .chosen
+            .iter()
+            .map(|&g| search.groups[g].clone())
+            .collect()
+    })
+}
+
+struct Badges(Option<Vec<Group>>);
+
+impl fmt::Display for Badges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(groups) = &self.0 else {
+            return write!(f, "no grouping gives each group exactly one badge");
+        };
+        let total: usize = groups.iter().map(|(_, badge)| badge).sum();
+        write!(f, "badge priorities sum to {total}")?;
+        for (group, badge) in groups {
+            let item = match *badge {
+                p @ 1..=26 => (b'a' + p as u8 - 1) as char,
+                p => (b'A' + p as u8 - 27) as char,
+            };
+            let members = group
+                .iter()
+                .map(|i| (i + 1).to_string())
+                .collect::<Vec<_>>();
+            write!(f, "\n  {item}: rucksacks {}", members.join(", "))?;
+        }
+        Ok(())
     }
 }
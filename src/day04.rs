@@ -1,42 +1,374 @@
+use crate::parsing::{parse_lines, scan_as};
 use crate::{bail, Aoc, Day04, Display, FileRep, Result};
+use std::cmp::Ordering::*;
+use std::fmt;
 
 impl Aoc for Day04 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
-        let res: Result<u32> = lines
-            .iter()
-            .map::<Result<u32>, _>(|l| {
-                let [a, b, x, y] = parse_line(l)?[..] else {
-                    bail!("parse error");
-                };
-                Ok(u32::from((a <= x && y <= b) || (x <= a && b <= y)))
-            })
-            .sum();
-        result!(res?)
+        let relations = classify(lines)?;
+        result!(relations.iter().filter(|r| r.is_containment()).count())
     }
 
     fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
-        let mut res = 0;
-        for l in lines {
-            if let [a, b, x, y] = parse_line(l)?[..] {
-                if (a <= x && x <= b) || (x <= a && a <= y) {
-                    res += 1;
+        let relations = classify(lines)?;
+        result!(relations.iter().filter(|r| r.shares_sections()).count())
+    }
+
+    fn report(&self, input: &FileRep) -> Result<Option<Box<dyn Display>>> {
+        if !self.report && self.coverage.is_none() {
+            return Ok(None);
+        }
+        let lines = &input.byte_lines;
+        let mut res = String::new();
+        if self.report {
+            let relations = classify(lines)?;
+            for r in Relation::ALL {
+                let count = relations.iter().filter(|&&x| x == r).count();
+                res.push_str(&format!("\n  {r}: {count}"));
+            }
+        }
+        if let Some(k) = self.coverage {
+            let pairs = parse_lines(lines, |l| parse_line(l))?;
+            res.push_str(&format!("\n{}", Coverage::new(&pairs, k)));
+        }
+        Ok(Some(Box::new(res)))
+    }
+}
+
+// Allen's interval relations, reading each range of sections a-b as the
+// interval [a, b + 1), so that adjacent ranges meet without sharing a
+// section
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Relation {
+    Before,
+    Meets,
+    Overlaps,
+    Starts,
+    During,
+    Finishes,
+    Equals,
+    FinishedBy,
+    Contains,
+    StartedBy,
+    OverlappedBy,
+    MetBy,
+    After,
+}
+
+use Relation::*;
+
+impl Relation {
+    const ALL: [Relation; 13] = [
+        Before,
+        Meets,
+        Overlaps,
+        Starts,
+        During,
+        Finishes,
+        Equals,
+        FinishedBy,
+        Contains,
+        StartedBy,
+        OverlappedBy,
+        MetBy,
+        After,
+    ];
+
+    // relation of the first range a-b to the second range x-y
+    fn between([a, b, x, y]: [u32; 4]) -> Relation {
+        let (b, y) = (b + 1, y + 1);
+        match (a.cmp(&x), b.cmp(&y)) {
+            (Equal, Equal) => Equals,
+            (Equal, Less) => Starts,
+            (Equal, Greater) => StartedBy,
+            (Greater, Equal) => Finishes,
+            (Less, Equal) => FinishedBy,
+            (Greater, Less) => During,
+            (Less, Greater) => Contains,
+            (Less, Less) => match b.cmp(&x) {
+                Less => Before,
+                Equal => Meets,
+                Greater => Overlaps,
+            },
+            (Greater, Greater) => match a.cmp(&y) {
+                Greater => After,
+                Equal => MetBy,
+                Less => OverlappedBy,
+            },
+        }
+    }
+
+    // one of the ranges includes the other
+    fn is_containment(self) -> bool {
+        matches!(
+            self,
+            Starts | During | Finishes | Equals | FinishedBy | Contains | StartedBy
+        )
+    }
+
+    fn shares_sections(self) -> bool {
+        !matches!(self, Before | Meets | MetBy | After)
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Before => "before",
+            Meets => "meets",
+            Overlaps => "overlaps",
+            Starts => "starts",
+            During => "during",
+            Finishes => "finishes",
+            Equals => "equals",
+            FinishedBy => "finished by",
+            Contains => "contains",
+            StartedBy => "started by",
+            OverlappedBy => "overlapped by",
+            MetBy => "met by",
+            After => "after",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// How all the assignments together cover the sections between the lowest
+// and highest assigned ones.
+struct Coverage {
+    // maximal runs of sections with the same number of elves, as
+    // (first, last, depth)
+    profile: Vec<(u32, u32, usize)>,
+    k: usize,
+    nelves: usize,
+    // 0-based indices of the pairs whose two ranges are each included in
+    // the range of an elf from another pair
+    redundant: Vec<usize>,
+}
+
+impl Coverage {
+    fn new(pairs: &[[u32; 4]], k: usize) -> Coverage {
+        let ranges: Vec<(u32, u32)> = pairs
+            .iter()
+            .flat_map(|&[a, b, x, y]| [(a, b), (x, y)])
+            .collect();
+
+        let mut events: Vec<(u32, isize)> = ranges
+            .iter()
+            .flat_map(|&(a, b)| [(a, 1), (b + 1, -1)])
+            .collect();
+        events.sort_unstable();
+        let mut profile: Vec<(u32, u32, usize)> = vec![];
+        let mut depth = 0;
+        for w in events.windows(2) {
+            depth += w[0].1;
+            let (first, next) = (w[0].0, w[1].0);
+            if first == next {
+                continue;
+            }
+            match profile.last_mut() {
+                Some(last) if last.2 == depth as usize && last.1 + 1 == first => last.1 = next - 1,
+                _ => profile.push((first, next - 1, depth as usize)),
+            }
+        }
+
+        // With ranges sorted by start then decreasing end, a range is
+        // included in another one iff it is included in one of the ranges
+        // before its run of identical ranges, or that run holds a range of
+        // another pair.
+        let mut order: Vec<usize> = (0..ranges.len()).collect();
+        order.sort_unstable_by_key(|&i| (ranges[i].0, std::cmp::Reverse(ranges[i].1)));
+        let mut covered = vec![false; ranges.len()];
+        // largest end among earlier ranges, along with the pair it belongs
+        // to, and the largest end among ranges of other pairs
+        let mut best: Option<(u32, usize)> = None;
+        let mut other: Option<u32> = None;
+        for run in order.chunk_by(|&i, &j| ranges[i] == ranges[j]) {
+            let b = ranges[run[0]].1;
+            for &i in run {
+                let end = match best {
+                    Some((end, pair)) if pair != i / 2 => Some(end),
+                    _ => other,
+                };
+                covered[i] = end.is_some_and(|e| e >= b) || run.iter().any(|&j| j / 2 != i / 2);
+            }
+            for &i in run {
+                match best {
+                    Some((end, pair)) if end >= b => {
+                        if pair != i / 2 {
+                            other = other.max(Some(b));
+                        }
+                    }
+                    Some((end, pair)) => {
+                        if pair != i / 2 {
+                            other = other.max(Some(end));
+                        }
+                        best = Some((b, i / 2));
+                    }
+                    None => best = Some((b, i / 2)),
                 }
-            } else {
-                bail!("parse error");
             }
         }
-        result!(res)
+
+        Coverage {
+            profile,
+            k,
+            nelves: ranges.len(),
+            redundant: (0..pairs.len())
+                .filter(|&p| covered[2 * p] && covered[2 * p + 1])
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the first few items of a long list
+        fn list(items: &[String]) -> String {
+            const SHOWN: usize = 20;
+            let mut res = items[..items.len().min(SHOWN)].join(", ");
+            if items.len() > SHOWN {
+                res.push_str(&format!(", ... {} more", items.len() - SHOWN));
+            }
+            res
+        }
+
+        // number of sections in the runs, and the runs merged when adjacent
+        fn runs<'a>(runs: impl Iterator<Item = &'a (u32, u32, usize)>) -> String {
+            let mut merged: Vec<(u32, u32)> = vec![];
+            for &(first, last, _) in runs {
+                match merged.last_mut() {
+                    Some(prev) if prev.1 + 1 == first => prev.1 = last,
+                    _ => merged.push((first, last)),
+                }
+            }
+            if merged.is_empty() {
+                return "none".to_string();
+            }
+            let n: u32 = merged.iter().map(|(first, last)| last - first + 1).sum();
+            let ranges: Vec<String> = merged
+                .iter()
+                .map(|&(first, last)| match first == last {
+                    true => first.to_string(),
+                    false => format!("{first}-{last}"),
+                })
+                .collect();
+            format!("{n} ({})", list(&ranges))
+        }
+
+        let (Some(first), Some(last)) = (self.profile.first(), self.profile.last()) else {
+            return write!(f, "no assignments");
+        };
+        writeln!(
+            f,
+            "{} elves assigned to sections {}-{}",
+            self.nelves, first.0, last.1
+        )?;
+        let uncovered = runs(self.profile.iter().filter(|r| r.2 == 0));
+        writeln!(f, "  covered by no one: {uncovered}")?;
+        let crowded = runs(self.profile.iter().filter(|r| r.2 > self.k));
+        writeln!(f, "  covered by more than {} elves: {crowded}", self.k)?;
+        let depth = self.profile.iter().map(|r| r.2).max().unwrap_or(0);
+        let deepest = runs(self.profile.iter().filter(|r| r.2 == depth));
+        writeln!(f, "  maximum depth {depth}, over {deepest}")?;
+        let lines: Vec<String> = self.redundant.iter().map(|p| (p + 1).to_string()).collect();
+        write!(
+            f,
+            "  redundant pairs: {} (lines {})",
+            lines.len(),
+            list(&lines)
+        )
+    }
+}
+
+fn classify(lines: &[&[u8]]) -> Result<Vec<Relation>> {
+    parse_lines(lines, |l| Ok(Relation::between(parse_line(l)?)))
+}
+
+fn parse_line(line: &[u8]) -> Result<[u32; 4]> {
+    let [a, b, x, y] = scan_as(line, "{}-{},{}-{}")?;
+    if a > b || x > y {
+        bail!(
+            "parse error: empty range in {:?}",
+            String::from_utf8_lossy(line)
+        );
     }
+    Ok([a, b, x, y])
 }
 
-fn parse_line(line: &[u8]) -> Result<Vec<u32>> {
-    line.split(|c| *c == b',')
-        .flat_map(|s| s.split(|c| *c == b'-'))
-        .map::<Result<u32>, _>(|s| {
-            let s = std::str::from_utf8(s)?;
-            Ok(s.parse()?)
-        })
-        .collect()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redundant(pairs: &[[u32; 4]]) -> Vec<usize> {
+        Coverage::new(pairs, 1).redundant
+    }
+
+    #[test]
+    fn example_redundant_pairs() {
+        let pairs = [
+            [2, 4, 6, 8],
+            [2, 3, 4, 5],
+            [5, 7, 7, 9],
+            [2, 8, 3, 7],
+            [6, 6, 4, 6],
+            [2, 6, 4, 8],
+        ];
+        assert_eq!(redundant(&pairs), [0, 1, 4, 5]);
+    }
+
+    #[test]
+    fn identical_ranges_of_one_pair_covered_by_another() {
+        assert_eq!(redundant(&[[2, 4, 2, 4], [2, 4, 10, 12]]), [0]);
+    }
+
+    #[test]
+    fn identical_ranges_within_a_pair_do_not_cover_each_other() {
+        assert_eq!(
+            redundant(&[[2, 4, 2, 4], [5, 6, 10, 12]]),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn redundant_pairs_match_brute_force() {
+        let mut seed = 7_u64;
+        let mut next = |n: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+        for _ in 0..200 {
+            let pairs: Vec<[u32; 4]> = (0..1 + next(6))
+                .map(|_| {
+                    let [a, x] = [next(5) as u32 + 1, next(5) as u32 + 1];
+                    [a, a + next(3) as u32, x, x + next(3) as u32]
+                })
+                .collect();
+            let ranges: Vec<(u32, u32)> = pairs
+                .iter()
+                .flat_map(|&[a, b, x, y]| [(a, b), (x, y)])
+                .collect();
+            let covered = |i: usize| {
+                (0..ranges.len()).any(|j| {
+                    j / 2 != i / 2 && ranges[j].0 <= ranges[i].0 && ranges[i].1 <= ranges[j].1
+                })
+            };
+            let expected: Vec<usize> = (0..pairs.len())
+                .filter(|&p| covered(2 * p) && covered(2 * p + 1))
+                .collect();
+            assert_eq!(redundant(&pairs), expected, "{pairs:?}");
+        }
+    }
+
+    #[test]
+    fn profile_merges_runs_of_equal_depth() {
+        let coverage = Coverage::new(&[[1, 2, 5, 6], [1, 2, 8, 8]], 1);
+        assert_eq!(
+            coverage.profile,
+            [(1, 2, 2), (3, 4, 0), (5, 6, 1), (7, 7, 0), (8, 8, 1)]
+        );
+    }
 }
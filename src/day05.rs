@@ -1,98 +1,430 @@
-use crate::{eyre, Aoc, Day05, Display, FileRep, Result};
+use crate::parsing::{paragraphs, parse_lines_from, scan_as, value};
+use crate::visualize::{self, Frame};
+use crate::{bail, ensure, eyre, Aoc, Day05, Display, FileRep, Result, WrapErr};
+use tracing::instrument;
 
 impl Aoc for Day05 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
-        let lines = &input.byte_lines;
-        let mut parsed = None;
-        for i in 0..lines.len() {
-            if lines[i].is_empty() {
-                parsed = Some((&lines[0..i], &lines[i + 1..]));
-            }
-        }
-        let (crates, moves) = parsed.ok_or_else(|| eyre!("parse error"))?;
-        let mut crates = parse_crates(crates)?;
-        let moves = parse_moves(moves)?;
-
-        for (m, s, t) in moves {
-            for _ in 0..m {
-                let c = crates[s - 1].pop().ok_or_else(|| eyre!("error"))?;
-                crates[t - 1].push(c);
-            }
-        }
+        result!(tops(&run(&CrateMover9000, &input.byte_lines)?))
+    }
+
+    fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
+        result!(tops(&run(&CrateMover9001, &input.byte_lines)?))
+    }
 
+    fn report(&self, input: &FileRep) -> Result<Option<Box<dyn Display>>> {
         let mut res = String::new();
+        if self.report {
+            let (crates, moves) = split_input(&input.byte_lines)?;
+            let moves = parse_moves(crates.len() + 2, moves)?;
+            let start = parse_crates(crates)?;
+            let step = self.step.unwrap_or(moves.len()).min(moves.len());
+
+            // the crane of each part, one after the other
+            for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+                let mut sim = Simulator::new(crane, start.clone());
+                sim.run(&moves)?;
+                while sim.done > step {
+                    sim.undo()?;
+                }
+                res.push_str(&format!(
+                    "\nafter {step} of {} moves with the {}:\n",
+                    moves.len(),
+                    crane.name()
+                ));
+                for row in sim.render() {
+                    res.push_str(&format!("{}\n", String::from_utf8_lossy(&row)));
+                }
+                while sim.redo()? {}
 
-        for mut cc in crates {
-            if let Some(top) = cc.pop() {
-                res.push(top as char);
+                let mut stacks = sim.stacks;
+                unsimulate(crane, &mut stacks, &moves)?;
+                let restored = if stacks == start { "yes" } else { "no" };
+                res.push_str(&format!(
+                    "undoing every move restores the drawing: {restored}"
+                ));
             }
         }
+        if let Some(capacity) = self.capacity {
+            let crane = LimitedCrane::new(capacity)?;
+            let stacks = run(&crane, &input.byte_lines)?;
+            res.push_str(&format!("\n{}: {}", crane.name(), tops(&stacks)));
+        }
+        Ok((!res.is_empty()).then(|| Box::new(res) as Box<dyn Display>))
+    }
+}
 
-        result!(res)
+/// How a crane carries crates from one stack to another.
+trait Crane {
+    fn name(&self) -> String;
+
+    /// Moves the top `n` crates of `from` onto `to`, which the simulator
+    /// checked `from` holds.
+    fn carry(&self, n: usize, from: &mut Vec<u8>, to: &mut Vec<u8>);
+
+    /// Puts back the `n` crates a `carry` between the same stacks moved,
+    /// which the simulator checked `to` holds.
+    fn uncarry(&self, n: usize, from: &mut Vec<u8>, to: &mut Vec<u8>) {
+        self.carry(n, to, from);
     }
+}
 
-    fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
-        let lines = &input.byte_lines;
-        let mut parsed = None;
-        for i in 0..lines.len() {
-            if lines[i].is_empty() {
-                parsed = Some((&lines[0..i], &lines[i + 1..]));
-            }
+/// Carries one crate at a time, reversing their order.
+struct CrateMover9000;
+
+/// Carries all the crates at once, keeping their order.
+struct CrateMover9001;
+
+/// Carries up to `capacity` crates at a time, keeping the order of each
+/// load.
+struct LimitedCrane {
+    capacity: usize,
+}
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".to_string()
+    }
+
+    fn carry(&self, n: usize, from: &mut Vec<u8>, to: &mut Vec<u8>) {
+        to.extend(from.drain(from.len() - n..).rev());
+    }
+}
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".to_string()
+    }
+
+    fn carry(&self, n: usize, from: &mut Vec<u8>, to: &mut Vec<u8>) {
+        to.extend(from.drain(from.len() - n..));
+    }
+}
+
+impl LimitedCrane {
+    fn new(capacity: usize) -> Result<LimitedCrane> {
+        ensure!(capacity > 0, "a crane must lift at least one crate");
+        Ok(LimitedCrane { capacity })
+    }
+}
+
+impl Crane for LimitedCrane {
+    fn name(&self) -> String {
+        format!("crane lifting {} crates", self.capacity)
+    }
+
+    fn carry(&self, n: usize, from: &mut Vec<u8>, to: &mut Vec<u8>) {
+        let mut left = n;
+        while left > 0 {
+            let load = left.min(self.capacity);
+            to.extend(from.drain(from.len() - load..));
+            left -= load;
+        }
+    }
+
+    // the loads come back last one first, and the last one may be partial
+    fn uncarry(&self, n: usize, from: &mut Vec<u8>, to: &mut Vec<u8>) {
+        let mut load = n % self.capacity;
+        if load == 0 {
+            load = self.capacity.min(n);
         }
-        let (crates, moves) = parsed.ok_or_else(|| eyre!("parse error"))?;
-        let mut crates = parse_crates(crates)?;
-        let moves = parse_moves(moves)?;
-
-        for (m, s, t) in moves {
-            let len = crates[s - 1].len();
-            let mut top = crates[s - 1].split_off(len - m);
-            crates[t - 1].append(&mut top);
-            // crates[t-1].drain(&crates[s-1][len-m..len]);
-            // let top = crates[s-1].rchunks(m).next().ok_or_else(|| eyre!("error"))?;
-            // crates[t-1].drain(top).collect();
+        let mut left = n;
+        while left > 0 {
+            from.extend(to.drain(to.len() - load..));
+            left -= load;
+            load = self.capacity.min(left);
         }
+    }
+}
 
-        let mut res = String::new();
+#[derive(Copy, Clone, Debug)]
+struct Move {
+    count: usize,
+    from: usize,
+    to: usize,
+    /// Line of the move in the input.
+    line: usize,
+}
 
-        for mut cc in crates {
-            if let Some(top) = cc.pop() {
-                res.push(top as char);
-            }
+fn run(crane: &dyn Crane, lines: Lines) -> Result<Vec<Vec<u8>>> {
+    let (crates, moves) = split_input(lines)?;
+    let moves = parse_moves(crates.len() + 2, moves)?;
+    let mut sim = Simulator::new(crane, parse_crates(crates)?);
+    sim.run(&moves)?;
+    Ok(sim.stacks)
+}
+
+// The stacks along with a log of the moves applied to them, which can be
+// undone and redone.
+struct Simulator<'a> {
+    crane: &'a dyn Crane,
+    stacks: Vec<Vec<u8>>,
+    log: Vec<Move>,
+    /// Number of moves of the log currently applied; the rest were undone.
+    done: usize,
+}
+
+impl<'a> Simulator<'a> {
+    fn new(crane: &'a dyn Crane, stacks: Vec<Vec<u8>>) -> Simulator<'a> {
+        Simulator {
+            crane,
+            stacks,
+            log: vec![],
+            done: 0,
+        }
+    }
+
+    // Applies the moves in order, rejecting the first one the stacks do not
+    // allow.
+    fn run(&mut self, moves: &[Move]) -> Result<()> {
+        for &m in moves {
+            self.step(m)?;
         }
+        Ok(())
+    }
+
+    // Applies a move, forgetting the moves undone before it.
+    fn step(&mut self, m: Move) -> Result<()> {
+        apply(self.crane, &mut self.stacks, &m).wrap_err_with(|| format!("on line {}", m.line))?;
+        self.log.truncate(self.done);
+        self.log.push(m);
+        self.done += 1;
+        self.show("move", &m)
+    }
+
+    // Undoes the last applied move, returning whether there was one.
+    fn undo(&mut self) -> Result<bool> {
+        let Some(done) = self.done.checked_sub(1) else {
+            return Ok(false);
+        };
+        let m = self.log[done];
+        unapply(self.crane, &mut self.stacks, &m)?;
+        self.done = done;
+        self.show("undo", &m)?;
+        Ok(true)
+    }
+
+    // Applies again the last undone move, returning whether there was one.
+    fn redo(&mut self) -> Result<bool> {
+        let Some(&m) = self.log.get(self.done) else {
+            return Ok(false);
+        };
+        apply(self.crane, &mut self.stacks, &m)?;
+        self.done += 1;
+        self.show("redo", &m)?;
+        Ok(true)
+    }
 
-        result!(res)
+    fn render(&self) -> Vec<Vec<u8>> {
+        render(&self.stacks)
     }
+
+    fn show(&self, what: &str, m: &Move) -> Result<()> {
+        visualize::emit(|| {
+            Frame::new(
+                format!(
+                    "day 5 {}: {what} {} from {} to {}",
+                    self.crane.name(),
+                    m.count,
+                    m.from,
+                    m.to
+                ),
+                self.render(),
+            )
+        })
+    }
+}
+
+// Turns the arrangement left by the moves back into the one they started
+// from, undoing them last one first.
+fn unsimulate(crane: &dyn Crane, stacks: &mut [Vec<u8>], moves: &[Move]) -> Result<()> {
+    for m in moves.iter().rev() {
+        unapply(crane, stacks, m).wrap_err_with(|| format!("on line {}", m.line))?;
+    }
+    Ok(())
 }
 
+fn apply(crane: &dyn Crane, stacks: &mut [Vec<u8>], m: &Move) -> Result<()> {
+    let (from, to) = stack_pair(stacks, m, m.from)?;
+    crane.carry(m.count, from, to);
+    Ok(())
+}
+
+fn unapply(crane: &dyn Crane, stacks: &mut [Vec<u8>], m: &Move) -> Result<()> {
+    let (from, to) = stack_pair(stacks, m, m.to)?;
+    crane.uncarry(m.count, from, to);
+    Ok(())
+}
+
+// The source and target stacks of a move, checking that they exist and
+// that stack `source` holds the crates to move.
+fn stack_pair<'s>(
+    stacks: &'s mut [Vec<u8>],
+    m: &Move,
+    source: usize,
+) -> Result<(&'s mut Vec<u8>, &'s mut Vec<u8>)> {
+    let n = stacks.len();
+    for stack in [m.from, m.to] {
+        ensure!(
+            (1..=n).contains(&stack),
+            "invalid move: no stack {stack}, stacks are 1 to {n}"
+        );
+    }
+    ensure!(m.from != m.to, "invalid move: stack {} onto itself", m.from);
+    let held = stacks[source - 1].len();
+    ensure!(
+        m.count <= held,
+        "invalid move: {} crates from stack {source}, which holds {held}",
+        m.count
+    );
+
+    Ok(if m.from < m.to {
+        let (left, right) = stacks.split_at_mut(m.to - 1);
+        (&mut left[m.from - 1], &mut right[0])
+    } else {
+        let (left, right) = stacks.split_at_mut(m.from - 1);
+        (&mut right[0], &mut left[m.to - 1])
+    })
+}
+
+// the top crate of each stack
+fn tops(stacks: &[Vec<u8>]) -> String {
+    stacks
+        .iter()
+        .filter_map(|s| s.last())
+        .map(|&c| c as char)
+        .collect()
+}
+
+type Lines<'a> = &'a [&'a [u8]];
+
+fn split_input<'a>(lines: Lines<'a>) -> Result<(Lines<'a>, Lines<'a>)> {
+    let mut parts = paragraphs(lines);
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(crates), Some(moves), None) => Ok((crates, moves)),
+        _ => Err(eyre!(
+            "parse error: expected crates and moves separated by an empty line"
+        )),
+    }
+}
+
+// Reads the drawing of the stacks. Each stack is identified by its label in
+// the bottom line, and a crate belongs to the stack whose label lies under
+// it, so rows may be ragged and labels may have several digits. Labels must
+// number the stacks from 1 in order.
+#[instrument(level = "debug", skip_all)]
 fn parse_crates(lines: &[&[u8]]) -> Result<Vec<Vec<u8>>> {
-    let mut stacks = lines.iter().rev();
-    let ncols: usize = (2 + stacks.next().ok_or_else(|| eyre!("parse error"))?.len()) / 4;
-    let mut res = vec![Vec::new(); ncols];
-
-    for row in stacks {
-        for i in 0..ncols {
-            let c = row[4 * i + 1];
-            if c != b' ' {
-                res[i].push(c);
+    let Some((labels, rows)) = lines.split_last() else {
+        bail!("parse error: no stacks drawn");
+    };
+    let label_line = lines.len();
+
+    // columns spanned by each label
+    let mut spans: Vec<(usize, usize)> = vec![];
+    let mut col = 0;
+    while col < labels.len() {
+        match labels[col] {
+            b' ' => col += 1,
+            b'0'..=b'9' => {
+                let start = col;
+                while labels.get(col).is_some_and(u8::is_ascii_digit) {
+                    col += 1;
+                }
+                let label: usize = value(&labels[start..col])?;
+                if label != spans.len() + 1 {
+                    bail!(
+                        "parse error: on line {label_line}: expected stack label {} at column {}, found {label}",
+                        spans.len() + 1,
+                        start + 1
+                    );
+                }
+                spans.push((start, col));
             }
+            c => bail!(
+                "parse error: on line {label_line}: unexpected {:?} at column {} of stack labels",
+                c as char,
+                col + 1
+            ),
+        }
+    }
+    if spans.is_empty() {
+        bail!("parse error: on line {label_line}: no stack labels");
+    }
+
+    let mut res = vec![Vec::new(); spans.len()];
+    for (i, row) in rows.iter().enumerate().rev() {
+        let mut col = 0;
+        while col < row.len() {
+            if row[col] == b' ' {
+                col += 1;
+                continue;
+            }
+            let (Some(b'['), Some(&c), Some(b']')) =
+                (row.get(col), row.get(col + 1), row.get(col + 2))
+            else {
+                bail!(
+                    "parse error: on line {}: expected a crate like \"[A]\" at column {} of {:?}",
+                    i + 1,
+                    col + 1,
+                    String::from_utf8_lossy(row)
+                );
+            };
+            let Some(stack) = spans.iter().position(|&(s, e)| s < col + 3 && col < e) else {
+                bail!(
+                    "parse error: on line {}: crate at column {} is above no stack label",
+                    i + 1,
+                    col + 1
+                );
+            };
+            if res[stack].len() != rows.len() - 1 - i {
+                bail!(
+                    "parse error: on line {}: crate {:?} floats above stack {}",
+                    i + 1,
+                    c as char,
+                    stack + 1
+                );
+            }
+            res[stack].push(c);
+            col += 3;
         }
     }
 
     Ok(res)
 }
 
-fn parse_moves(lines: &[&[u8]]) -> Result<Vec<(usize, usize, usize)>> {
-    lines
-        .iter()
-        .map::<Result<_>, _>(|l| {
-            let words = l.split(|c| *c == b' ').collect::<Vec<&[u8]>>();
-            let mut v = vec![];
-            for i in [1, 3, 5] {
-                let w = words[i];
-                let s = std::str::from_utf8(w)?;
-                v.push(s.parse()?);
-            }
-            Ok((v[0], v[1], v[2]))
+// draws the stacks the way the puzzle input does
+fn render(crates: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let height = crates.iter().map(|c| c.len()).max().unwrap_or(0);
+    let mut rows: Vec<Vec<u8>> = (0..height)
+        .rev()
+        .map(|h| {
+            let row = crates
+                .iter()
+                .map(|c| c.get(h).map_or(*b"   ", |&x| [b'[', x, b']']))
+                .collect::<Vec<_>>();
+            row.join(&b' ')
+        })
+        .collect();
+    let labels = (1..=crates.len())
+        .map(|i| format!("{i:^3}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    rows.push(labels.into_bytes());
+    rows
+}
+
+// the moves, numbering lines from `first`
+#[instrument(level = "debug", skip_all)]
+fn parse_moves(first: usize, lines: &[&[u8]]) -> Result<Vec<Move>> {
+    let moves = parse_lines_from(first, lines, |l| scan_as(l, "move {} from {} to {}"))?;
+    Ok(moves
+        .into_iter()
+        .zip(first..)
+        .map(|([count, from, to], line)| Move {
+            count,
+            from,
+            to,
+            line,
         })
-        .collect::<Result<Vec<_>>>()
+        .collect())
 }
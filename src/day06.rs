@@ -1,39 +1,267 @@
-use crate::{bail, Aoc, Day06, Display, FileRep, Result};
+use crate::{bail, ensure, eyre, Aoc, Day06, Display, FileRep, Result};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufReader, Bytes, Read};
 
 impl Aoc for Day06 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
-        let lines = &input.byte_lines;
-        let l = lines[0];
-        let mut count = 4;
-        for w in l.windows(4) {
-            if all_different(w) {
-                return result!(count);
+        result!(first_marker(signal(input)?, 4, &self.alphabet)?)
+    }
+
+    fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
+        result!(first_marker(signal(input)?, 14, &self.alphabet)?)
+    }
+
+    fn report(&self, input: &FileRep) -> Result<Option<Box<dyn Display>>> {
+        let Some(len) = self.marker else {
+            return Ok(None);
+        };
+        // only the first positions are kept, the others being counted
+        let (mut count, mut shown) = (0_u64, vec![]);
+        for p in Day06::markers_from_reader(signal(input)?, len, &self.alphabet)? {
+            let p = p?;
+            if shown.len() < 20 {
+                shown.push(p.to_string());
             }
             count += 1;
         }
-        bail!("No result could be found");
+        let mut res = format!(
+            "{count} markers of {len} symbols, ending at {}",
+            shown.join(", ")
+        );
+        if count > shown.len() as u64 {
+            res.push_str(&format!(", ... {} more", count - shown.len() as u64));
+        }
+        Ok(Some(Box::new(res)))
     }
+}
 
-    fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
-        let lines = &input.byte_lines;
-        let l = lines[0];
-        let mut count = 14;
-        for w in l.windows(14) {
-            if all_different(w) {
-                return result!(count);
+impl Default for Day06 {
+    fn default() -> Self {
+        Day06 {
+            marker: None,
+            alphabet: Alphabet::Bytes,
+        }
+    }
+}
+
+/// The symbols a signal is made of.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Alphabet {
+    /// Any byte.
+    Bytes,
+    /// Any character, the signal being UTF-8.
+    Chars,
+    /// Only the given characters, the signal being UTF-8. Any other
+    /// character is an error.
+    Only(Vec<char>),
+}
+
+impl Alphabet {
+    pub fn lowercase() -> Alphabet {
+        Alphabet::Only(('a'..='z').collect())
+    }
+}
+
+impl Day06 {
+    /// Positions, counted in symbols from the start of `reader`, at which
+    /// the last `len` symbols read are all different. The stream is read in
+    /// chunks and scanned in a single pass.
+    pub fn markers_from_reader<R: Read>(
+        reader: R,
+        len: usize,
+        alphabet: &Alphabet,
+    ) -> Result<impl Iterator<Item = Result<u64>>> {
+        ensure!(len > 0, "markers must be at least one symbol long");
+        let mut detector = Detector::new(len);
+        let mut symbols = symbols(reader, alphabet);
+        Ok(std::iter::from_fn(move || {
+            for symbol in symbols.by_ref() {
+                match symbol {
+                    Ok(s) if detector.push(s) => return Some(Ok(detector.pos)),
+                    Ok(_) => {}
+                    Err(e) => return Some(Err(e)),
+                }
             }
-            count += 1;
+            None
+        }))
+    }
+}
+
+// The signal read from `reader` as indices of symbols: byte values, code
+// points, or positions in an explicit alphabet.
+fn symbols<R: Read>(reader: R, alphabet: &Alphabet) -> Symbols<R> {
+    let mut index = HashMap::new();
+    if let Alphabet::Only(chars) = alphabet {
+        for (i, &c) in chars.iter().enumerate() {
+            index.entry(c).or_insert(i);
         }
-        bail!("No result could be found");
     }
+    Symbols {
+        bytes: BufReader::with_capacity(1 << 16, reader).bytes(),
+        alphabet: alphabet.clone(),
+        index,
+        offset: 0,
+    }
+}
+
+struct Symbols<R> {
+    bytes: Bytes<BufReader<R>>,
+    alphabet: Alphabet,
+    /// Position of each character of an explicit alphabet.
+    index: HashMap<char, usize>,
+    /// Number of bytes read so far.
+    offset: u64,
 }
 
-fn all_different(bytes: &[u8]) -> bool {
-    let mut counter = [0_u8; 256];
-    for &b in bytes {
-        counter[b as usize] += 1;
+impl<R: Read> Symbols<R> {
+    fn next_byte(&mut self) -> Result<Option<u8>> {
+        self.offset += 1;
+        Ok(self.bytes.next().transpose()?)
+    }
+
+    fn next_symbol(&mut self) -> Result<Option<usize>> {
+        let Some(b) = self.next_byte()? else {
+            return Ok(None);
+        };
+        if self.alphabet == Alphabet::Bytes {
+            return Ok(Some(b as usize));
+        }
+
+        let start = self.offset;
+        let width = match b {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => bail!("parse error: invalid utf-8 at byte {start}"),
+        };
+        let mut buf = [b, 0, 0, 0];
+        for slot in &mut buf[1..width] {
+            *slot = self.next_byte()?.unwrap_or_default();
+        }
+        let Some(c) = std::str::from_utf8(&buf[..width])
+            .ok()
+            .and_then(|s| s.chars().next())
+        else {
+            bail!("parse error: invalid utf-8 at byte {start}");
+        };
+
+        match self.alphabet {
+            Alphabet::Only(_) => match self.index.get(&c) {
+                Some(&i) => Ok(Some(i)),
+                None => bail!("parse error: {c:?} at byte {start} is not in the alphabet"),
+            },
+            _ => Ok(Some(c as usize)),
+        }
+    }
+}
+
+impl<R: Read> Iterator for Symbols<R> {
+    type Item = Result<usize>;
+
+    fn next(&mut self) -> Option<Result<usize>> {
+        self.next_symbol().transpose()
+    }
+}
+
+fn signal<'a>(input: &FileRep<'a>) -> Result<&'a [u8]> {
+    input
+        .byte_lines
+        .first()
+        .copied()
+        .ok_or_else(|| eyre!("parse error: empty input"))
+}
+
+fn first_marker(signal: &[u8], len: usize, alphabet: &Alphabet) -> Result<u64> {
+    Day06::markers_from_reader(signal, len, alphabet)?
+        .next()
+        .unwrap_or_else(|| Err(eyre!("no marker of {len} symbols in the signal")))
+}
+
+// The last `len` symbols of a stream, along with how many times each
+// symbol occurs among them and how many occur more than once.
+struct Detector {
+    len: usize,
+    /// The window, which grows up to `len` symbols as they arrive.
+    window: VecDeque<usize>,
+    /// Occurrences in the window, indexed by symbol and grown as needed.
+    counts: Vec<u32>,
+    repeated: usize,
+    /// Number of symbols pushed so far.
+    pos: u64,
+}
+
+impl Detector {
+    fn new(len: usize) -> Detector {
+        Detector {
+            len,
+            window: VecDeque::new(),
+            counts: vec![0; 256],
+            repeated: 0,
+            pos: 0,
+        }
+    }
+
+    // Adds the next symbol of the stream, returning whether the window is
+    // now full of different symbols.
+    fn push(&mut self, b: usize) -> bool {
+        if self.window.len() == self.len {
+            let old = self.window.pop_front().unwrap();
+            self.counts[old] -= 1;
+            if self.counts[old] == 1 {
+                self.repeated -= 1;
+            }
+        }
+        if b >= self.counts.len() {
+            self.counts.resize(b + 1, 0);
+        }
+        self.window.push_back(b);
+        self.counts[b] += 1;
+        if self.counts[b] == 2 {
+            self.repeated += 1;
+        }
+        self.pos += 1;
+        self.window.len() == self.len && self.repeated == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers(signal: &[u8], len: usize, alphabet: &Alphabet) -> Result<Vec<u64>> {
+        Day06::markers_from_reader(signal, len, alphabet)?.collect()
+    }
+
+    #[test]
+    fn repeated_uppercase_letters_and_digits() {
+        assert_eq!(markers(b"AABCD", 4, &Alphabet::Bytes).unwrap(), [5]);
+        assert!(markers(b"ABAB", 3, &Alphabet::Bytes).unwrap().is_empty());
+        assert_eq!(markers(b"1123", 3, &Alphabet::Bytes).unwrap(), [4]);
+        assert_eq!(markers(b"a1A1", 3, &Alphabet::Bytes).unwrap(), [3]);
+    }
+
+    #[test]
+    fn high_bytes() {
+        let signal = [0x80, 0x80, 0xff, 0xfe, 0xff];
+        assert_eq!(markers(&signal, 2, &Alphabet::Bytes).unwrap(), [3, 4, 5]);
+        assert_eq!(markers(&signal, 3, &Alphabet::Bytes).unwrap(), [4]);
+    }
+
+    #[test]
+    fn multibyte_characters() {
+        let signal = "éé€x€".as_bytes();
+        assert_eq!(markers(signal, 2, &Alphabet::Chars).unwrap(), [3, 4, 5]);
+        assert_eq!(markers(signal, 3, &Alphabet::Chars).unwrap(), [4]);
+    }
+
+    #[test]
+    fn explicit_alphabet() {
+        let abc = Alphabet::Only(vec!['a', 'b', 'c']);
+        assert_eq!(markers(b"aabca", 3, &abc).unwrap(), [4, 5]);
+        let err = markers(b"abz", 2, &abc).unwrap_err();
+        assert!(err.to_string().contains("not in the alphabet"), "{err}");
+        let err = markers(b"ab\xc3", 2, &abc).unwrap_err();
+        assert!(err.to_string().contains("invalid utf-8"), "{err}");
     }
-    counter[b'a' as usize..=b'z' as usize]
-        .iter()
-        .all(|c| *c <= 1)
 }
@@ -1,6 +1,11 @@
-use crate::{bail, Aoc, Day07, Display, FileRep, Result};
+use crate::parsing::value;
+use crate::{bail, eyre, Aoc, Day07, Display, FileRep, Result, WrapErr};
+use tracing::{instrument, warn};
 
 use std::collections::hash_map::HashMap;
+use std::collections::BTreeSet;
+use std::io::ErrorKind;
+use std::path::Path;
 
 type Fid = usize;
 
@@ -36,80 +41,588 @@ impl File {
             size: Some(size),
         }
     }
+
+    // the size once computed, which directories only have after
+    // `compute_size`
+    fn size(&self) -> usize {
+        self.size.unwrap_or(0)
+    }
 }
 
 impl Aoc for Day07 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
-        let lines = &input.byte_lines;
-        let mut files = parse(lines)?;
-        compute_size(&mut files, 0)?;
-        let res: usize = files
-            .iter()
-            .filter_map(|f| f.is_dir.then_some(f.size?))
-            .filter(|&u| u <= 100000)
-            .sum();
+        let fs = parse(&input.byte_lines)?;
+        let res: usize = fs.dirs().map(File::size).filter(|&u| u <= 100000).sum();
 
         result!(res)
     }
 
     fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
-        let lines = &input.byte_lines;
-        let mut files = parse(lines)?;
-        compute_size(&mut files, 0)?;
-        let root_size = files[0].size.unwrap();
-        let free_space = 70000000 - root_size;
-        let needed = 30000000 - free_space;
-        let res = files
-            .iter()
-            .filter_map(|f| f.is_dir.then_some(f.size?))
+        let fs = parse(&input.byte_lines)?;
+        let used = fs.files[ROOT].size();
+        let Some(free_space) = self.disk_size.checked_sub(used) else {
+            bail!("{used} used on a disk of {}", self.disk_size);
+        };
+        let needed = self.needed.saturating_sub(free_space);
+        let res = fs
+            .dirs()
+            .map(File::size)
             .filter(|&u| u >= needed)
             .min()
-            .unwrap();
+            .ok_or_else(|| eyre!("no directory frees {needed}"))?;
 
         result!(res)
     }
+
+    fn report(&self, input: &FileRep) -> Result<Option<Box<dyn Display>>> {
+        if self.query.is_none() && self.export.is_none() {
+            return Ok(None);
+        }
+        let fs = parse(&input.byte_lines)?;
+        let mut res = String::new();
+        for w in &fs.warnings {
+            res.push_str(&format!("\nwarning: {w}"));
+        }
+        if let Some(query) = &self.query {
+            res.push_str(&format!("\n{}", fs.query(query)?));
+        }
+        if let Some(root) = &self.export {
+            res.push_str(&format!("\n{}", fs.export(root)?));
+        }
+        Ok(Some(Box::new(res)))
+    }
 }
 
-fn parse(lines: &[&[u8]]) -> Result<Vec<File>> {
-    let mut files: Vec<File> = vec![];
-    let mut pwd = 0;
-    files.push(File::dir("/", 0, 0));
+impl Day07 {
+    /// The terminal session of the puzzle listing the real directory
+    /// `dir`, entering every subdirectory in name order. Only regular files
+    /// and directories are listed.
+    pub fn transcript_from_dir(dir: &Path) -> Result<String> {
+        let mut res = String::from("$ cd /\n");
+        list_dir(dir, &mut res)?;
+        Ok(res)
+    }
+}
 
-    for &line in lines {
-        let line = std::str::from_utf8(line)?;
-        let words = line.split_ascii_whitespace().collect::<Vec<&str>>();
-        if words[0] == "$" {
-            match words[1] {
-                "cd" => match words[2] {
-                    "/" => pwd = 0,
-                    ".." => pwd = files[pwd].parent_id,
-                    s => {
-                        pwd = *(files[pwd].children.as_ref().unwrap().get(s).unwrap());
-                    }
-                },
-                "ls" => (),
-                _ => bail!("invalid command"),
+fn list_dir(dir: &Path, res: &mut String) -> Result<()> {
+    let mut files = vec![];
+    let mut dirs = vec![];
+    for entry in
+        std::fs::read_dir(dir).wrap_err_with(|| format!("cannot list {}", dir.display()))?
+    {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            bail!("{} is not valid utf-8", entry.path().display());
+        };
+        if name.contains(char::is_whitespace) {
+            bail!("{} has whitespace in its name", entry.path().display());
+        }
+        if meta.is_dir() {
+            dirs.push(name);
+        } else if meta.is_file() {
+            files.push((name, meta.len()));
+        }
+    }
+    dirs.sort();
+    files.sort();
+
+    res.push_str("$ ls\n");
+    for d in &dirs {
+        res.push_str(&format!("dir {d}\n"));
+    }
+    for (f, size) in &files {
+        res.push_str(&format!("{size} {f}\n"));
+    }
+    for d in &dirs {
+        res.push_str(&format!("$ cd {d}\n"));
+        list_dir(&dir.join(d), res)?;
+        res.push_str("$ cd ..\n");
+    }
+    Ok(())
+}
+
+impl Default for Day07 {
+    fn default() -> Self {
+        Day07 {
+            disk_size: 70000000,
+            needed: 30000000,
+            query: None,
+            export: None,
+        }
+    }
+}
+
+const ROOT: Fid = 0;
+
+// The filesystem rebuilt from a transcript, with the size of every
+// directory computed.
+struct Fs {
+    files: Vec<File>,
+    /// Oddities of the transcript that did not prevent rebuilding it.
+    warnings: Vec<String>,
+}
+
+impl Fs {
+    fn new() -> Fs {
+        Fs {
+            files: vec![File::dir("/", ROOT, ROOT)],
+            warnings: vec![],
+        }
+    }
+
+    // Adds a file, or a directory if it has no size, to directory `parent`.
+    fn add(&mut self, parent: Fid, name: &str, size: Option<usize>) -> Fid {
+        let fid = self.files.len();
+        let file = match size {
+            Some(size) => File::regular_file(name, fid, parent, size),
+            None => File::dir(name, fid, parent),
+        };
+        self.files.push(file);
+        if let Some(children) = self.files[parent].children.as_mut() {
+            children.insert(name.to_string(), fid);
+        }
+        fid
+    }
+
+    fn child(&self, fid: Fid, name: &str) -> Option<Fid> {
+        self.files[fid].children.as_ref()?.get(name).copied()
+    }
+
+    fn dirs(&self) -> impl Iterator<Item = &File> {
+        self.files.iter().filter(|f| f.is_dir)
+    }
+
+    // children of a directory sorted by name
+    fn children(&self, fid: Fid) -> Vec<&File> {
+        let mut res: Vec<&File> = self.files[fid]
+            .children
+            .iter()
+            .flat_map(|c| c.values())
+            .map(|&c| &self.files[c])
+            .collect();
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+        res
+    }
+
+    // Follows `path` from directory `cwd`, or from the root if it starts
+    // with `/`.
+    fn resolve(&self, cwd: Fid, path: &str) -> Result<Fid> {
+        let mut fid = if path.starts_with('/') { ROOT } else { cwd };
+        for name in path.split('/').filter(|&n| !n.is_empty() && n != ".") {
+            fid = match name {
+                ".." => self.files[fid].parent_id,
+                _ => self
+                    .child(fid, name)
+                    .ok_or_else(|| eyre!("no such file or directory: {path}"))?,
             };
-            continue;
-        } else if words[0] == "dir" {
-            let fid = files.len();
-            let children = files[pwd].children.as_mut().unwrap();
-            if !children.contains_key(words[1]) {
-                children.insert(words[1].to_string(), fid);
-                files.push(File::dir(words[1], fid, pwd));
+        }
+        Ok(fid)
+    }
+
+    fn path(&self, fid: Fid) -> String {
+        let mut names = vec![];
+        let mut fid = fid;
+        while fid != ROOT {
+            names.push(self.files[fid].name.as_str());
+            fid = self.files[fid].parent_id;
+        }
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    // draws the tree below `fid` the way the puzzle statement does
+    fn tree(&self, fid: Fid) -> String {
+        let mut res = String::new();
+        let mut todo = vec![(fid, 0)];
+        while let Some((fid, depth)) = todo.pop() {
+            let f = &self.files[fid];
+            let kind = if f.is_dir { "dir" } else { "file" };
+            res.push_str(&format!(
+                "{}- {} ({kind}, size={})\n",
+                "  ".repeat(depth),
+                f.name,
+                f.size()
+            ));
+            todo.extend(self.children(fid).iter().rev().map(|c| (c.id, depth + 1)));
+        }
+        res
+    }
+
+    // the directories below `fid`, largest first
+    fn du(&self, fid: Fid) -> Vec<(usize, String)> {
+        let mut res: Vec<(usize, String)> = self
+            .find(fid, &Find::default().dirs())
+            .into_iter()
+            .map(|d| (self.files[d].size(), self.path(d)))
+            .collect();
+        res.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        res
+    }
+
+    // the files below `fid`, including itself, matching `pred`
+    fn find(&self, fid: Fid, pred: &Find) -> Vec<Fid> {
+        let mut res = vec![];
+        let mut todo = vec![fid];
+        while let Some(fid) = todo.pop() {
+            if pred.matches(&self.files[fid]) {
+                res.push(fid);
+            }
+            todo.extend(self.children(fid).iter().rev().map(|c| c.id));
+        }
+        res
+    }
+
+    // Recreates the tree in a new directory under `root`, files being
+    // sparse files of their listed size, and checks that the size of every
+    // directory is the apparent size `du` would find on disk.
+    fn export(&self, root: &Path) -> Result<String> {
+        std::fs::create_dir_all(root)
+            .wrap_err_with(|| format!("cannot create {}", root.display()))?;
+        let mut i = 0;
+        let base = loop {
+            let dir = root.join(format!("fs{i}"));
+            match std::fs::create_dir(&dir) {
+                Ok(()) => break dir,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => i += 1,
+                Err(e) => {
+                    return Err(e).wrap_err_with(|| format!("cannot create {}", dir.display()))
+                }
+            }
+        };
+
+        let disk_path = |fid: Fid| base.join(self.path(fid).trim_start_matches('/'));
+        for f in &self.files[1..] {
+            if f.name.is_empty() || f.name == "." || f.name == ".." || f.name.contains('/') {
+                bail!("cannot export {}: invalid name", self.path(f.id));
+            }
+            let path = disk_path(f.id);
+            if f.is_dir {
+                std::fs::create_dir(&path)
+            } else {
+                std::fs::File::create(&path).and_then(|file| file.set_len(f.size() as u64))
+            }
+            .wrap_err_with(|| format!("cannot create {}", path.display()))?;
+        }
+
+        let mut mismatches = vec![];
+        for d in self.dirs() {
+            let on_disk = disk_usage(&disk_path(d.id))?;
+            if on_disk != d.size() as u64 {
+                mismatches.push(format!(
+                    "{}: {} on disk, {} computed",
+                    self.path(d.id),
+                    on_disk,
+                    d.size()
+                ));
+            }
+        }
+        let mut res = format!(
+            "exported {} directories and {} files to {}",
+            self.dirs().count(),
+            self.files.len() - self.dirs().count(),
+            base.display()
+        );
+        match mismatches.is_empty() {
+            true => res.push_str(", every directory size matches the disk"),
+            false => res.push_str(&format!(", sizes differ for {}", mismatches.join("; "))),
+        }
+        Ok(res)
+    }
+
+    // Answers a query of the form `tree [PATH]`, `du [PATH]` or
+    // `find [PATH] PREDICATE...`, paths being relative to the root.
+    fn query(&self, query: &str) -> Result<String> {
+        let mut words = query.split_whitespace().peekable();
+        let command = words.next().unwrap_or_default();
+        let path = match words.peek() {
+            Some(w) if !w.contains(['=', '<', '>']) => words.next().unwrap_or("/"),
+            _ => "/",
+        };
+        let fid = self.resolve(ROOT, path)?;
+
+        Ok(match command {
+            "tree" => self.tree(fid),
+            "du" => self
+                .du(fid)
+                .iter()
+                .map(|(size, path)| format!("{size:>10}  {path}\n"))
+                .collect(),
+            "find" => {
+                let pred = words.map(str::parse).collect::<Result<Vec<Find>>>()?;
+                let pred = pred.into_iter().fold(Find::default(), Find::and);
+                self.find(fid, &pred)
+                    .iter()
+                    .map(|&f| format!("{:>10}  {}\n", self.files[f].size(), self.path(f)))
+                    .collect()
+            }
+            _ => bail!("unknown query {query:?}, expected tree, du or find"),
+        })
+    }
+}
+
+// Conditions on the files listed by `find`, all of which must hold.
+#[derive(Clone, Debug, Default)]
+struct Find {
+    /// Patterns for the name, where `*` matches any text and `?` any one
+    /// character.
+    names: Vec<String>,
+    /// Required kinds, `true` for directories; conflicting kinds match
+    /// nothing.
+    is_dir: Vec<bool>,
+    min_size: Option<usize>,
+    max_size: Option<usize>,
+}
+
+impl Find {
+    fn dirs(mut self) -> Find {
+        self.is_dir.push(true);
+        self
+    }
+
+    fn and(mut self, other: Find) -> Find {
+        self.names.extend(other.names);
+        self.is_dir.extend(other.is_dir);
+        self.min_size = self.min_size.max(other.min_size);
+        self.max_size = match (self.max_size, other.max_size) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self
+    }
+
+    fn matches(&self, f: &File) -> bool {
+        self.names
+            .iter()
+            .all(|p| glob(p.as_bytes(), f.name.as_bytes()))
+            && self.is_dir.iter().all(|&d| d == f.is_dir)
+            && self.min_size.is_none_or(|m| f.size() >= m)
+            && self.max_size.is_none_or(|m| f.size() <= m)
+    }
+}
+
+// one of `name=PATTERN`, `type=d`, `type=f`, `size>N`, `size<N` or `size=N`
+impl std::str::FromStr for Find {
+    type Err = crate::Report;
+
+    fn from_str(s: &str) -> Result<Find> {
+        let find = Find::default();
+        Ok(if let Some(name) = s.strip_prefix("name=") {
+            Find {
+                names: vec![name.to_string()],
+                ..find
+            }
+        } else if let Some(kind) = s.strip_prefix("type=") {
+            Find {
+                is_dir: vec![match kind {
+                    "d" => true,
+                    "f" => false,
+                    _ => bail!("invalid file type {kind:?}, expected d or f"),
+                }],
+                ..find
+            }
+        } else if let Some(n) = s.strip_prefix("size>") {
+            Find {
+                min_size: Some(
+                    value::<usize, _>(n)?
+                        .checked_add(1)
+                        .ok_or_else(|| eyre!("size>{n} matches no file"))?,
+                ),
+                ..find
+            }
+        } else if let Some(n) = s.strip_prefix("size<") {
+            Find {
+                max_size: Some(
+                    value::<usize, _>(n)?
+                        .checked_sub(1)
+                        .ok_or_else(|| eyre!("size<0 matches no file"))?,
+                ),
+                ..find
+            }
+        } else if let Some(n) = s.strip_prefix("size=") {
+            let n = value(n)?;
+            Find {
+                min_size: Some(n),
+                max_size: Some(n),
+                ..find
             }
         } else {
-            let size = words[0].parse::<usize>()?;
-            let fid = files.len();
-            let children = files[pwd].children.as_mut().unwrap();
-            if !children.contains_key(words[1]) {
-                children.insert(words[1].to_string(), fid);
-                files.push(File::regular_file(words[1], fid, pwd, size));
+            bail!("invalid predicate {s:?}, expected name=, type= or size")
+        })
+    }
+}
+
+fn glob(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, _) => name.is_empty(),
+        (Some((b'*', rest)), _) => {
+            glob(rest, name) || (!name.is_empty() && glob(pattern, &name[1..]))
+        }
+        (Some((&p, rest)), Some((&c, name))) => (p == b'?' || p == c) && glob(rest, name),
+        (Some(_), None) => false,
+    }
+}
+
+// The directory whose listing is being read, the line of its `ls`, and
+// the names listed so far.
+struct Listing {
+    dir: Fid,
+    line: usize,
+    names: BTreeSet<String>,
+}
+
+// Rebuilds the filesystem from a terminal transcript. Directories entered
+// before being listed are created, and a directory listed again must list
+// the same entries. Unknown commands are skipped along with their output.
+#[instrument(level = "debug", skip_all)]
+fn parse(lines: &[&[u8]]) -> Result<Fs> {
+    let mut fs = Fs::new();
+    let mut pwd = ROOT;
+    // previous listing of each directory
+    let mut listings: HashMap<Fid, Listing> = HashMap::new();
+    // line each file was first listed on
+    let mut listed_on: HashMap<Fid, usize> = HashMap::new();
+    let mut listing: Option<Listing> = None;
+    let mut skipping = false;
+
+    for (n, &line) in (1..).zip(lines) {
+        let line = std::str::from_utf8(line).wrap_err_with(|| format!("on line {n}"))?;
+        let words = line.split_ascii_whitespace().collect::<Vec<&str>>();
+        if words.first() == Some(&"$") {
+            if let Some(done) = listing.take() {
+                check_listing(&fs, &mut listings, done)?;
+            }
+            skipping = false;
+        }
+
+        match (&words[..], listing.as_mut()) {
+            ([], _) => {}
+            (["$", "cd", path], _) => pwd = cd(&mut fs, &listings, pwd, path, n)?,
+            (["$", "ls"], _) => {
+                listing = Some(Listing {
+                    dir: pwd,
+                    line: n,
+                    names: BTreeSet::new(),
+                })
+            }
+            (["$", ..], _) => {
+                let w = format!("line {n}: skipping unknown command {line:?} and its output");
+                warn!("{w}");
+                fs.warnings.push(w);
+                skipping = true;
+            }
+            (&[kind, name], Some(current)) => {
+                let size = match kind {
+                    "dir" => None,
+                    _ => Some(value(kind).wrap_err_with(|| format!("on line {n}"))?),
+                };
+                let describe = |size: Option<usize>| match size {
+                    Some(size) => format!("a file of size {size}"),
+                    None => "a directory".to_string(),
+                };
+                match fs.child(current.dir, name) {
+                    None => {
+                        let fid = fs.add(current.dir, name, size);
+                        listed_on.insert(fid, n);
+                    }
+                    Some(fid) => {
+                        let old = fs.files[fid].size.filter(|_| !fs.files[fid].is_dir);
+                        if old != size {
+                            let path = fs.path(fid);
+                            let place = match listed_on.get(&fid) {
+                                Some(m) => format!("on line {m}"),
+                                None => "when entered".to_string(),
+                            };
+                            bail!(
+                                "on line {n}: {path} is listed as {}, but was {} {place}",
+                                describe(size),
+                                describe(old)
+                            );
+                        }
+                        listed_on.entry(fid).or_insert(n);
+                    }
+                }
+                current.names.insert(name.to_string());
+            }
+            _ if skipping => {}
+            _ => bail!("on line {n}: unexpected {line:?} outside of a listing"),
+        }
+    }
+    if let Some(done) = listing.take() {
+        check_listing(&fs, &mut listings, done)?;
+    }
+
+    compute_size(&mut fs.files, ROOT)?;
+    Ok(fs)
+}
+
+// Follows `path` from `pwd` like `cd` would, creating the directories that
+// were not listed yet.
+fn cd(
+    fs: &mut Fs,
+    listings: &HashMap<Fid, Listing>,
+    pwd: Fid,
+    path: &str,
+    n: usize,
+) -> Result<Fid> {
+    let mut fid = if path.starts_with('/') { ROOT } else { pwd };
+    for name in path.split('/').filter(|&n| !n.is_empty() && n != ".") {
+        fid = match (name, fs.child(fid, name)) {
+            ("..", _) => fs.files[fid].parent_id,
+            (_, Some(child)) if fs.files[child].is_dir => child,
+            (_, Some(child)) => bail!("on line {n}: cannot cd into file {}", fs.path(child)),
+            (_, None) => {
+                if let Some(l) = listings.get(&fid) {
+                    let w = format!(
+                        "line {n}: entering {name}, which the listing of {} on line {} did not show",
+                        fs.path(fid),
+                        l.line
+                    );
+                    warn!("{w}");
+                    fs.warnings.push(w);
+                }
+                fs.add(fid, name, None)
+            }
+        };
+    }
+    Ok(fid)
+}
+
+// Records a finished listing, checking that it agrees with any earlier
+// listing of the same directory.
+fn check_listing(fs: &Fs, listings: &mut HashMap<Fid, Listing>, done: Listing) -> Result<()> {
+    if let Some(old) = listings.get(&done.dir) {
+        if old.names != done.names {
+            let mut diff = vec![];
+            for (what, a, b) in [("lacks", old, &done), ("adds", &done, old)] {
+                let names: Vec<&str> = a.names.difference(&b.names).map(|s| s.as_str()).collect();
+                if !names.is_empty() {
+                    diff.push(format!("{what} {}", names.join(", ")));
+                }
             }
+            bail!(
+                "on line {}: listing of {} {} compared to the one on line {}",
+                done.line,
+                fs.path(done.dir),
+                diff.join(" and "),
+                old.line
+            );
         }
+        return Ok(());
     }
+    listings.insert(done.dir, done);
+    Ok(())
+}
 
-    Ok(files)
+// total apparent size of the files below `path`, like `du -sb`
+fn disk_usage(path: &Path) -> Result<u64> {
+    let meta = std::fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(if meta.is_file() { meta.len() } else { 0 });
+    }
+    std::fs::read_dir(path)?
+        .map(|entry| disk_usage(&entry?.path()))
+        .sum()
 }
 
 fn compute_size(files: &mut [File], fid: Fid) -> Result<usize> {
@@ -124,3 +637,36 @@ fn compute_size(files: &mut [File], fid: Fid) -> Result<usize> {
     files[fid].size = Some(size);
     Ok(size)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(preds: &str) -> Result<Find> {
+        let preds = preds
+            .split(' ')
+            .map(str::parse)
+            .collect::<Result<Vec<Find>>>()?;
+        Ok(preds.into_iter().fold(Find::default(), Find::and))
+    }
+
+    #[test]
+    fn every_predicate_holds() {
+        let txt = File::regular_file("d.txt", 1, 0, 10);
+        let log = File::regular_file("d.log", 2, 0, 10);
+        let dir = File::dir("d", 3, 0);
+
+        let names = find("name=*.txt name=d*").unwrap();
+        assert!(names.matches(&txt));
+        assert!(!names.matches(&log) && !names.matches(&dir));
+
+        let kinds = find("type=d type=f").unwrap();
+        assert!(!kinds.matches(&txt) && !kinds.matches(&dir));
+        assert!(!find("type=f").unwrap().dirs().matches(&txt));
+
+        assert!(find("size>9 size<11").unwrap().matches(&txt));
+        assert!(!find("size>10").unwrap().matches(&txt));
+        assert!(find("size<0").is_err());
+        assert!(find(&format!("size>{}", usize::MAX)).is_err());
+    }
+}
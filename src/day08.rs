@@ -1,9 +1,235 @@
-use crate::{Aoc, Day08, Display, FileRep, Result};
-use std::collections::hash_set::HashSet as Set;
+use crate::parsing::scan_as;
+use crate::visualize::{self, Frame};
+use crate::{bail, ensure, Aoc, Day08, Display, FileRep, Result};
+use tracing::instrument;
 
 impl Aoc for Day08 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
+        let forest = parse(lines)?;
+        let visible = visibility(&forest, self);
+        let res = visible.iter().filter(|&&v| v).count();
+        visualize::emit(|| {
+            let best = best_tree(&scenic_scores(&forest, self));
+            let cells = visible
+                .iter()
+                .map(|&v| if v { (b'#', VISIBLE) } else { (b'.', HIDDEN) })
+                .collect();
+            heatmap(&forest, format!("day 8: {res} visible trees"), cells, best)
+        })?;
+
+        result!(res)
+    }
+
+    fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
+        let lines = &input.byte_lines;
+        let forest = parse(lines)?;
+        let scores = scenic_scores(&forest, self);
+        let res = scores.iter().copied().max().unwrap_or(0);
+        visualize::emit(|| {
+            // on a log scale, scores being products of distances
+            let scale = |s: usize| ((1 + s) as f64).ln() / ((1 + res) as f64).ln().max(1.0);
+            let cells = scores
+                .iter()
+                .map(|&s| {
+                    let t = scale(s);
+                    (b'0' + (9.0 * t).round() as u8, ramp(t))
+                })
+                .collect();
+            heatmap(
+                &forest,
+                format!("day 8: best scenic score {res}"),
+                cells,
+                best_tree(&scores),
+            )
+        })?;
+
+        result!(res)
+    }
+}
+
+impl Default for Day08 {
+    fn default() -> Self {
+        Day08 {
+            directions: CARDINAL.to_vec(),
+            see_over: 1,
+            max_distance: None,
+        }
+    }
+}
+
+const CARDINAL: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const DIAGONAL: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+impl Day08 {
+    /// Directions given as `4`, `8`, or steps `di,dj` separated by spaces,
+    /// `di` going down the rows and `dj` right along them.
+    pub fn directions(spec: &str) -> Result<Vec<(isize, isize)>> {
+        match spec.trim() {
+            "4" => Ok(CARDINAL.to_vec()),
+            "8" => Ok(CARDINAL.iter().chain(&DIAGONAL).copied().collect()),
+            spec => spec
+                .split_whitespace()
+                .map(|step| {
+                    let (di, dj) = scan_as(step, "{},{}")?.into();
+                    ensure!((di, dj) != (0, 0), "a direction cannot be 0,0");
+                    Ok((di, dj))
+                })
+                .collect(),
+        }
+    }
+
+    // whether a tree of height `other` blocks the view of one of height `h`
+    fn blocks(&self, other: u8, h: u8) -> bool {
+        other as usize + self.see_over > h as usize
+    }
+}
+
+// Tree heights, row by row.
+struct Forest {
+    width: usize,
+    height: usize,
+    trees: Vec<u8>,
+}
+
+impl Forest {
+    // Indices of the trees along every line of sight looking in direction
+    // `(di, dj)`, each line listed from the end the view goes to.
+    fn lines(&self, (di, dj): (isize, isize)) -> impl Iterator<Item = Vec<usize>> + '_ {
+        let (w, h) = (self.width as isize, self.height as isize);
+        let inside = move |i: isize, j: isize| (0..h).contains(&i) && (0..w).contains(&j);
+        (0..h)
+            .flat_map(move |i| (0..w).map(move |j| (i, j)))
+            .filter(move |&(i, j)| !inside(i + di, j + dj))
+            .map(move |(i, j)| {
+                (0..)
+                    .map(|k| (i - k * di, j - k * dj))
+                    .take_while(|&(i, j)| inside(i, j))
+                    .map(|(i, j)| (i * w + j) as usize)
+                    .collect()
+            })
+    }
+}
+
+// Whether each tree can be seen from outside the forest: no tree before it
+// along some line blocks the view.
+fn visibility(forest: &Forest, rules: &Day08) -> Vec<bool> {
+    let mut res = vec![false; forest.trees.len()];
+    for &d in &rules.directions {
+        for line in forest.lines(d) {
+            let mut tallest = None;
+            for i in line {
+                let h = forest.trees[i];
+                if tallest.is_none_or(|t| !rules.blocks(t, h)) {
+                    res[i] = true;
+                }
+                tallest = tallest.max(Some(h));
+            }
+        }
+    }
+    res
+}
+
+// The scenic score of each tree. Along each line, a stack keeps the trees
+// not hidden by a later one, tallest at the bottom, so that the tree
+// blocking the view is found in amortized constant time.
+fn scenic_scores(forest: &Forest, rules: &Day08) -> Vec<usize> {
+    let mut res = vec![1; forest.trees.len()];
+    for &d in &rules.directions {
+        for line in forest.lines(d) {
+            let mut stack: Vec<usize> = vec![];
+            for (k, &i) in line.iter().enumerate() {
+                let h = forest.trees[i];
+                while stack
+                    .last()
+                    .is_some_and(|&t| !rules.blocks(forest.trees[line[t]], h))
+                {
+                    stack.pop();
+                }
+                let distance = k - stack.last().copied().unwrap_or(0);
+                res[i] *= rules.max_distance.map_or(distance, |m| distance.min(m));
+                stack.push(k);
+            }
+        }
+    }
+    res
+}
+
+const VISIBLE: [u8; 3] = [70, 180, 90];
+const HIDDEN: [u8; 3] = [25, 45, 30];
+const BEST: [u8; 3] = [255, 40, 220];
+
+// position of the tree with the highest score, the first one on ties
+fn best_tree(scores: &[usize]) -> Option<usize> {
+    let best = scores.iter().copied().max()?;
+    scores.iter().position(|&s| s == best)
+}
+
+// colour for a value scaled to [0, 1], from dark blue to yellow
+fn ramp(t: f64) -> [u8; 3] {
+    let (low, high) = ([20.0, 30.0, 90.0], [250.0, 220.0, 60.0]);
+    [0, 1, 2].map(|i| (low[i] + t.clamp(0.0, 1.0) * (high[i] - low[i])) as u8)
+}
+
+// draws a character and colour per tree, marking the best tree with `X`
+fn heatmap(
+    forest: &Forest,
+    mut title: String,
+    mut cells: Vec<(u8, [u8; 3])>,
+    best: Option<usize>,
+) -> Frame {
+    if let Some(b) = best {
+        cells[b] = (b'X', BEST);
+        let (i, j) = (b / forest.width, b % forest.width);
+        title.push_str(&format!(", best tree at row {} column {}", i + 1, j + 1));
+    }
+    let rows = cells.chunks(forest.width.max(1));
+    Frame::new(
+        title,
+        rows.clone()
+            .map(|r| r.iter().map(|c| c.0).collect::<Vec<_>>()),
+    )
+    .with_colors(rows.map(|r| r.iter().map(|c| c.1).collect()).collect())
+}
+
+#[instrument(level = "debug", skip_all)]
+fn parse(lines: &[&[u8]]) -> Result<Forest> {
+    let width = lines.first().map_or(0, |l| l.len());
+    let mut trees = Vec::with_capacity(width * lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        if line.len() != width {
+            bail!(
+                "parse error: on line {}: {} trees, expected {width}",
+                i + 1,
+                line.len()
+            );
+        }
+        if let Some(j) = line.iter().position(|c| !c.is_ascii_digit()) {
+            bail!(
+                "parse error: on line {}: {:?} at column {} is not a height",
+                i + 1,
+                line[j] as char,
+                j + 1
+            );
+        }
+        trees.extend(line.iter().map(|c| c - b'0'));
+    }
+    Ok(Forest {
+        width,
+        height: lines.len(),
+        trees,
+    })
+}
+
+// The first, quadratic, implementation, which the dense computations above
+// are checked against, and a walk along every direction from each tree for
+// any other rules.
+#[cfg(test)]
+mod reference {
+    use crate::Day08;
+    use std::collections::hash_set::HashSet as Set;
+
+    pub fn visible(lines: &[&[u8]]) -> Set<(usize, usize)> {
         let height = lines.len();
         let width = lines[0].len();
         let mut visible = Set::<(usize, usize)>::new();
@@ -52,55 +278,164 @@ impl Aoc for Day08 {
             }
         }
 
-        result!(visible.len())
+        visible
     }
 
-    fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
-        let lines = &input.byte_lines;
-        let height = lines.len();
-        let width = lines[0].len();
+    pub fn score(grid: &[&[u8]], i: usize, j: usize) -> usize {
+        let height = grid.len();
+        let width = grid[0].len();
 
-        let res = (0..height)
-            .flat_map(|i| (0..width).map(move |j| score(lines, i, j)))
-            .max()
-            .unwrap();
+        let mut up = 0;
+        for x in (0..i).rev() {
+            up += 1;
+            if grid[i][j] <= grid[x][j] {
+                break;
+            }
+        }
+        let mut down = 0;
+        for x in i + 1..height {
+            down += 1;
+            if grid[i][j] <= grid[x][j] {
+                break;
+            }
+        }
+        let mut left = 0;
+        for y in (0..j).rev() {
+            left += 1;
+            if grid[i][j] <= grid[i][y] {
+                break;
+            }
+        }
+        let mut right = 0;
+        for y in j + 1..width {
+            right += 1;
+            if grid[i][j] <= grid[i][y] {
+                break;
+            }
+        }
 
-        result!(res)
+        up * down * left * right
+    }
+
+    // whether the tree at `i, j` is visible and its scenic score
+    pub fn view(grid: &[&[u8]], rules: &Day08, i: usize, j: usize) -> (bool, usize) {
+        let (height, width) = (grid.len() as isize, grid[0].len() as isize);
+        let tree = |y: isize, x: isize| grid[y as usize][x as usize] - b'0';
+        let h = tree(i as isize, j as isize);
+        let (mut visible, mut score) = (false, 1);
+        for &(di, dj) in &rules.directions {
+            let (mut y, mut x) = (i as isize + di, j as isize + dj);
+            let (mut distance, mut blocked) = (0, false);
+            while (0..height).contains(&y) && (0..width).contains(&x) {
+                distance += 1;
+                if rules.blocks(tree(y, x), h) {
+                    blocked = true;
+                    break;
+                }
+                (y, x) = (y + di, x + dj);
+            }
+            visible |= !blocked;
+            score *= rules.max_distance.map_or(distance, |m| distance.min(m));
+        }
+        (visible, score)
     }
 }
 
-fn score(grid: &[&[u8]], i: usize, j: usize) -> usize {
-    let height = grid.len();
-    let width = grid[0].len();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [&[u8]; 5] = [b"30373", b"25512", b"65332", b"33549", b"35390"];
 
-    let mut up = 0;
-    for x in (0..i).rev() {
-        up += 1;
-        if grid[i][j] <= grid[x][j] {
-            break;
+    // checks the dense results against the reference implementation
+    fn check(lines: &[&[u8]]) {
+        let forest = parse(lines).unwrap();
+        let rules = Day08::default();
+        let visible = visibility(&forest, &rules);
+        let scores = scenic_scores(&forest, &rules);
+        let expected = reference::visible(lines);
+        for i in 0..forest.height {
+            for j in 0..forest.width {
+                let k = i * forest.width + j;
+                assert_eq!(
+                    visible[k],
+                    expected.contains(&(i, j)),
+                    "visibility of {i},{j}"
+                );
+                assert_eq!(scores[k], reference::score(lines, i, j), "score of {i},{j}");
+            }
         }
     }
-    let mut down = 0;
-    for x in i + 1..height {
-        down += 1;
-        if grid[i][j] <= grid[x][j] {
-            break;
+
+    // checks the dense results against a walk from every tree under `rules`
+    fn check_rules(lines: &[&[u8]], rules: &Day08) {
+        let forest = parse(lines).unwrap();
+        let visible = visibility(&forest, rules);
+        let scores = scenic_scores(&forest, rules);
+        for i in 0..forest.height {
+            for j in 0..forest.width {
+                let k = i * forest.width + j;
+                let expected = reference::view(lines, rules, i, j);
+                assert_eq!(
+                    (visible[k], scores[k]),
+                    expected,
+                    "tree {i},{j} under {:?}, see over {}, max distance {:?}",
+                    rules.directions,
+                    rules.see_over,
+                    rules.max_distance
+                );
+            }
         }
     }
-    let mut left = 0;
-    for y in (0..j).rev() {
-        left += 1;
-        if grid[i][j] <= grid[i][y] {
-            break;
-        }
+
+    fn rule_sets() -> Vec<Day08> {
+        let rules = |directions: &str, see_over, max_distance| Day08 {
+            directions: Day08::directions(directions).unwrap(),
+            see_over,
+            max_distance,
+        };
+        vec![
+            Day08::default(),
+            rules("8", 1, None),
+            rules("2,1 -1,3 0,-2", 1, None),
+            rules("4", 0, None),
+            rules("4", 3, None),
+            rules("4", 1, Some(2)),
+            rules("8", 2, Some(3)),
+        ]
     }
-    let mut right = 0;
-    for y in j + 1..width {
-        right += 1;
-        if grid[i][j] <= grid[i][y] {
-            break;
-        }
+
+    #[test]
+    fn example() {
+        check(&EXAMPLE);
+        let forest = parse(&EXAMPLE).unwrap();
+        let rules = Day08::default();
+        assert_eq!(
+            visibility(&forest, &rules).iter().filter(|&&v| v).count(),
+            21
+        );
+        assert_eq!(scenic_scores(&forest, &rules).into_iter().max(), Some(8));
     }
 
-    up * down * left * right
+    #[test]
+    fn generated_grids() {
+        let mut seed = 1_u64;
+        for (height, width, max) in [(1, 1, 9), (1, 7, 9), (6, 1, 3), (9, 9, 2), (17, 23, 9)] {
+            let rows: Vec<Vec<u8>> = (0..height)
+                .map(|_| {
+                    (0..width)
+                        .map(|_| {
+                            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                            b'0' + ((seed >> 33) % (max + 1)) as u8
+                        })
+                        .collect()
+                })
+                .collect();
+            let lines: Vec<&[u8]> = rows.iter().map(|r| r.as_slice()).collect();
+            check(&lines);
+            for rules in rule_sets() {
+                check_rules(&lines, &rules);
+            }
+        }
+    }
 }
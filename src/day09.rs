@@ -1,92 +1,141 @@
-use crate::{bail, Aoc, Day09, Display, FileRep, Result};
+use crate::parsing::{parse_lines, scan, value};
+use crate::visualize::{self, Frame};
+use crate::{bail, ensure, Aoc, Day09, Display, FileRep, Result};
 use std::collections::hash_set::HashSet as Set;
+use tracing::instrument;
 
 impl Aoc for Day09 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
         let moves = parse(lines)?;
-        let res = run_nknots::<2>(&moves);
-        result!(res)
+        let rope = Rope::simulate(2, &moves)?;
+        result!(rope.tail_visits())
     }
 
     fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
         let moves = parse(lines)?;
-        let res = run_nknots::<10>(&moves);
-        result!(res)
+        let rope = Rope::simulate(self.knots, &moves)?;
+        result!(rope.tail_visits())
     }
-}
 
-fn parse(lines: &[&[u8]]) -> Result<Vec<(u8, u32)>> {
-    lines
-        .iter()
-        .map(|l| {
-            let dir = match (l[0], l[1]) {
-                (c, b' ') if c == b'U' || c == b'D' || c == b'L' || c == b'R' => c,
-                _ => bail!("parse error"),
+    fn report(&self, input: &FileRep) -> Result<Option<Box<dyn Display>>> {
+        if !self.report {
+            return Ok(None);
+        }
+        let moves = parse(&input.byte_lines)?;
+        let rope = Rope::simulate(self.knots, &moves)?;
+        let mut res = format!("distinct cells visited by each of {} knots:", self.knots);
+        for (i, cells) in rope.visited.iter().enumerate() {
+            let knot = if i == 0 {
+                "head".to_string()
+            } else {
+                format!("knot {i}")
             };
-            let nsteps = std::str::from_utf8(&l[2..])?.parse::<u32>()?;
-            Ok((dir, nsteps))
-        })
-        .collect()
+            res.push_str(&format!("\n  {knot}: {}", cells.len()));
+        }
+        Ok(Some(Box::new(res)))
+    }
 }
 
-fn run_nknots<const NKNOTS: usize>(moves: &[(u8, u32)]) -> usize {
-    let mut knots: [(isize, isize); NKNOTS] = [(0, 0); NKNOTS];
-    let mut visited: Set<(isize, isize)> = Set::new();
-    visited.insert((0, 0));
+impl Default for Day09 {
+    fn default() -> Self {
+        Day09 {
+            knots: 10,
+            report: false,
+        }
+    }
+}
 
-    for &(dir, nsteps) in moves {
-        let (x, y) = match dir {
-            b'U' => (0, 1),
-            b'D' => (0, -1),
-            b'L' => (-1, 0),
-            b'R' => (1, 0),
-            _ => panic!(),
-        };
+// Knots from the head, each following the one before it, and the cells
+// each of them visited.
+struct Rope {
+    knots: Vec<(isize, isize)>,
+    visited: Vec<Set<(isize, isize)>>,
+}
 
-        for _ in 0..nsteps {
-            knots[0].0 += x;
-            knots[0].1 += y;
+impl Rope {
+    fn new(nknots: usize) -> Result<Rope> {
+        ensure!(nknots > 0, "a rope needs at least one knot");
+        Ok(Rope {
+            knots: vec![(0, 0); nknots],
+            visited: vec![Set::from([(0, 0)]); nknots],
+        })
+    }
 
-            knots = move_rope::<NKNOTS>(knots);
+    // Runs all the moves on a rope of `nknots` knots starting at the origin.
+    fn simulate(nknots: usize, moves: &[(u8, u32)]) -> Result<Rope> {
+        let mut rope = Rope::new(nknots)?;
+        for &(dir, nsteps) in moves {
+            let step = match dir {
+                b'U' => (0, 1),
+                b'D' => (0, -1),
+                b'L' => (-1, 0),
+                b'R' => (1, 0),
+                _ => bail!("invalid direction {:?}", dir as char),
+            };
+            for _ in 0..nsteps {
+                rope.step(step);
+                visualize::emit(|| render(&rope.knots, rope.visited.last().unwrap()))?;
+            }
+        }
+        Ok(rope)
+    }
 
-            visited.insert(knots[NKNOTS - 1]);
+    // Moves the head one step, each knot then moving one step towards the
+    // knot before it when they no longer touch.
+    fn step(&mut self, (x, y): (isize, isize)) {
+        self.knots[0].0 += x;
+        self.knots[0].1 += y;
+        self.visited[0].insert(self.knots[0]);
+        for i in 1..self.knots.len() {
+            let (hx, hy) = self.knots[i - 1];
+            let (tx, ty) = self.knots[i];
+            let (dx, dy) = (hx - tx, hy - ty);
+            if dx.abs() <= 1 && dy.abs() <= 1 {
+                break;
+            }
+            self.knots[i] = (tx + dx.signum(), ty + dy.signum());
+            self.visited[i].insert(self.knots[i]);
         }
     }
 
-    visited.len()
+    fn tail_visits(&self) -> usize {
+        self.visited.last().map_or(0, Set::len)
+    }
 }
 
-fn move_rope<const NKNOTS: usize>(mut knots: [(isize, isize); NKNOTS]) -> [(isize, isize); NKNOTS] {
-    for i in 0..NKNOTS - 1 {
-        let (hx, hy) = knots[i];
-        let (mut tx, mut ty) = knots[i + 1];
+#[instrument(level = "debug", skip_all)]
+fn parse(lines: &[&[u8]]) -> Result<Vec<(u8, u32)>> {
+    parse_lines(lines, |l| {
+        let [dir, nsteps] = scan(l, "{} {}")?;
+        let dir = match dir {
+            "U" | "D" | "L" | "R" => dir.as_bytes()[0],
+            _ => bail!("parse error: invalid direction {dir:?}"),
+        };
+        Ok((dir, value(nsteps)?))
+    })
+}
 
-        if hx - tx == 2 {
-            tx += 1;
-            match ty.cmp(&hy) {
-                std::cmp::Ordering::Greater => ty += 1,
-                std::cmp::Ordering::Less => ty -= 1,
-                _ => {}
-            }
-        } else if hx - tx == -2 {
-            tx -= 1;
-            match ty.cmp(&hy) {
-                std::cmp::Ordering::Greater => ty += 1,
-                std::cmp::Ordering::Less => ty -= 1,
-                _ => {}
-            }
-        } else if hy - ty == 2 {
-            ty += 1;
-            tx = hx;
-        } else if hy - ty == -2 {
-            ty -= 1;
-            tx = hx;
-        }
+// the head is `H`, other knots are numbered and visited cells are `#`
+fn render(knots: &[(isize, isize)], visited: &Set<(isize, isize)>) -> Frame {
+    let cells = || knots.iter().chain(visited.iter()).chain([&(0, 0)]);
+    let min_x = cells().map(|p| p.0).min().unwrap_or(0);
+    let max_x = cells().map(|p| p.0).max().unwrap_or(0);
+    let min_y = cells().map(|p| p.1).min().unwrap_or(0);
+    let max_y = cells().map(|p| p.1).max().unwrap_or(0);
 
-        knots[i + 1] = (tx, ty);
+    let mut rows = vec![vec![b'.'; (max_x - min_x + 1) as usize]; (max_y - min_y + 1) as usize];
+    let mut draw = |(x, y): (isize, isize), c: u8| {
+        rows[(max_y - y) as usize][(x - min_x) as usize] = c;
+    };
+    for &p in visited {
+        draw(p, b'#');
+    }
+    draw((0, 0), b's');
+    for (i, &p) in knots.iter().enumerate().rev() {
+        draw(p, if i == 0 { b'H' } else { b'0' + (i % 10) as u8 });
     }
 
-    knots
+    Frame::new(format!("day 9: {} knots", knots.len()), rows)
 }
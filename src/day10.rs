@@ -1,4 +1,7 @@
-use crate::{bail, Aoc, Day10, Display, FileRep, Result};
+use crate::parsing::{parse_lines, scan_as};
+use crate::visualize::{self, Frame};
+use crate::{ocr, Aoc, Day10, Display, FileRep, Result};
+use tracing::{debug, instrument};
 
 impl Aoc for Day10 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
@@ -36,52 +39,75 @@ impl Aoc for Day10 {
 
     fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.byte_lines;
-        let instructions = parse(lines)?;
-
-        let instructions = instructions.into_iter().flat_map(|i| match i {
-            Some(x) => vec![None, Some(x)],
-            None => vec![None],
-        });
-
-        let pos = instructions.scan(1, |state, x| {
-            let old = *state;
-            if let Some(x) = x {
-                *state += x;
+        let display = render(&parse(lines)?);
+        for cycle in 1..=display.len() {
+            visualize::emit(|| {
+                Frame::new(
+                    format!("day 10: cycle {cycle}"),
+                    display[..cycle].chunks(40),
+                )
+            })?;
+        }
+        // screens that are not made of letters, like the example's, are
+        // shown as they are
+        match ocr::recognize(&display.chunks(40).collect::<Vec<_>>()) {
+            Ok(text) => result!(text),
+            Err(e) => {
+                debug!(error = %e, "unreadable screen");
+                result!(rows(&display))
             }
-            Some(old)
-        });
+        }
+    }
 
-        let raw_display = std::iter::repeat(0_i32..40_i32)
-            .flatten()
-            .zip(pos)
-            .map(|(i, x)| if (x - i).abs() <= 1 { b'#' } else { b'.' })
-            .collect::<Vec<u8>>();
+    fn report(&self, input: &FileRep) -> Result<Option<Box<dyn Display>>> {
+        if !self.report {
+            return Ok(None);
+        }
+        let lines = &input.byte_lines;
+        let display = render(&parse(lines)?);
+        Ok(Some(Box::new(rows(&display))))
+    }
+}
+
+// the screen as text, each row on its own line
+fn rows(display: &[u8]) -> String {
+    let mut res = String::new();
+    for row in display.chunks(40) {
+        res.push('\n');
+        res.push_str(&String::from_utf8_lossy(row));
+    }
+    res
+}
 
-        let display = raw_display
-            .chunks(40)
-            .map::<Result<_>, _>(|l| Ok(std::str::from_utf8(l)?))
-            .collect::<Result<Vec<&str>>>()?;
+// pixels drawn by the CRT, row after row
+fn render(instructions: &[Option<i32>]) -> Vec<u8> {
+    let instructions = instructions.iter().flat_map(|i| match i {
+        Some(x) => vec![None, Some(*x)],
+        None => vec![None],
+    });
 
-        let mut res = String::new();
-        for row in display {
-            res.push('\n');
-            res.push_str(row);
+    let pos = instructions.scan(1, |state, x| {
+        let old = *state;
+        if let Some(x) = x {
+            *state += x;
         }
+        Some(old)
+    });
 
-        result!(res)
-    }
+    std::iter::repeat(0_i32..40_i32)
+        .flatten()
+        .zip(pos)
+        .map(|(i, x)| if (x - i).abs() <= 1 { b'#' } else { b'.' })
+        .collect()
 }
 
+#[instrument(level = "debug", skip_all)]
 fn parse(lines: &[&[u8]]) -> Result<Vec<Option<i32>>> {
-    lines
-        .iter()
-        .map(|l| {
-            let instr = match &l[0..4] {
-                b"noop" => None,
-                b"addx" => Some(std::str::from_utf8(&l[5..])?.parse()?),
-                _ => bail!("parse error"),
-            };
-            Ok(instr)
-        })
-        .collect()
+    parse_lines(lines, |l| {
+        if *l == b"noop" {
+            return Ok(None);
+        }
+        let [x] = scan_as(l, "addx {}")?;
+        Ok(Some(x))
+    })
 }
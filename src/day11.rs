@@ -1,5 +1,7 @@
+use crate::parsing::{ints, paragraphs, scan, scan_as, value};
 use crate::{bail, Aoc, Day11, Display, FileRep, Result};
 use std::collections::VecDeque;
+use tracing::instrument;
 
 // shape of input: list of
 // Monkey 0:
@@ -32,7 +34,7 @@ impl Aoc for Day11 {
             for i in 0..monkeys.len() {
                 while let Some(item) = monkeys[i].items.pop_front() {
                     let item = (monkeys[i].op)(item) / 3;
-                    let next_monkey = if (item % monkeys[i].divisor) == 0 {
+                    let next_monkey = if item.is_multiple_of(monkeys[i].divisor) {
                         monkeys[i].monkey_true
                     } else {
                         monkeys[i].monkey_false
@@ -44,7 +46,7 @@ impl Aoc for Day11 {
         }
 
         counts.sort_unstable();
-        let [x,y] = counts.windows(2).last().unwrap()[..] else {
+        let [x, y] = counts.windows(2).last().unwrap()[..] else {
             panic!();
         };
         result!(x * y)
@@ -59,7 +61,7 @@ impl Aoc for Day11 {
             for i in 0..monkeys.len() {
                 while let Some(item) = monkeys[i].items.pop_front() {
                     let item = (monkeys[i].op)(item) % charac;
-                    let next_monkey = if (item % monkeys[i].divisor) == 0 {
+                    let next_monkey = if item.is_multiple_of(monkeys[i].divisor) {
                         monkeys[i].monkey_true
                     } else {
                         monkeys[i].monkey_false
@@ -71,7 +73,7 @@ impl Aoc for Day11 {
         }
 
         counts.sort_unstable();
-        let [x,y] = counts.windows(2).last().unwrap()[..] else {
+        let [x, y] = counts.windows(2).last().unwrap()[..] else {
             panic!();
         };
 
@@ -79,25 +81,32 @@ impl Aoc for Day11 {
     }
 }
 
+#[instrument(level = "debug", skip_all)]
 fn parse(lines: &[&[u8]]) -> Result<Vec<Monkey>> {
-    lines.split(|&l| l.is_empty()).map(parse_monkey).collect()
+    paragraphs(lines).map(parse_monkey).collect()
 }
 
 fn parse_monkey(lines: &[&[u8]]) -> Result<Monkey> {
-    let items: VecDeque<usize> = std::str::from_utf8(&lines[1][18..])?
-        .split(", ")
-        .map(|w| w.parse())
-        .collect::<Result<_, _>>()?;
-
-    let operator = match lines[2][23] {
-        b'+' => Operator::Plus,
-        b'*' => Operator::Times,
-        _ => bail!("parse error"),
+    let &[_, items, op, test, if_true, if_false] = lines else {
+        bail!(
+            "parse error: expected 6 lines per monkey, got {}",
+            lines.len()
+        );
     };
 
-    let operand = match std::str::from_utf8(&lines[2][25..])? {
+    let [items] = scan(items, "  Starting items: {}")?;
+    let items: VecDeque<usize> = ints(items)?.into();
+
+    let [operator, operand] = scan(op, "  Operation: new = old {} {}")?;
+    let operator = match operator {
+        "+" => Operator::Plus,
+        "*" => Operator::Times,
+        _ => bail!("parse error: invalid operator {operator:?}"),
+    };
+
+    let operand = match operand {
         "old" => None,
-        w => Some(w.parse::<usize>()?),
+        w => Some(value::<usize, _>(w)?),
     };
 
     let op = move |x: usize| match (operator, operand) {
@@ -109,9 +118,9 @@ fn parse_monkey(lines: &[&[u8]]) -> Result<Monkey> {
 
     let op = Box::new(op);
 
-    let divisor: usize = std::str::from_utf8(&lines[3][21..])?.parse()?;
-    let monkey_true: usize = std::str::from_utf8(&lines[4][29..])?.parse()?;
-    let monkey_false: usize = std::str::from_utf8(&lines[5][30..])?.parse()?;
+    let [divisor] = scan_as(test, "  Test: divisible by {}")?;
+    let [monkey_true] = scan_as(if_true, "    If true: throw to monkey {}")?;
+    let [monkey_false] = scan_as(if_false, "    If false: throw to monkey {}")?;
 
     Ok(Monkey {
         items,
@@ -3,8 +3,9 @@ use std::collections::hash_set::HashSet as Set;
 use std::collections::BinaryHeap as Heap;
 use std::collections::HashMap as Map;
 use std::fmt::Display;
+use tracing::{debug, trace};
 
-#[derive(Copy, Clone, PartialEq, Hash, Eq, Ord, PartialOrd)]
+#[derive(Copy, Clone, Debug, PartialEq, Hash, Eq, Ord, PartialOrd)]
 struct Point {
     x: usize,
     y: usize,
@@ -105,6 +106,7 @@ fn dijkstra(
                 continue;
             }
 
+            trace!(from = ?cur, to = ?p, cost = cur_cost + 1, "relax");
             res.insert(p, (el, cur_cost + 1));
             to_process.push(State {
                 p,
@@ -116,6 +118,8 @@ fn dijkstra(
         visited.insert(cur);
     }
 
+    debug!(?source, visited = visited.len(), "dijkstra done");
+
     res
 }
 
@@ -1,5 +1,7 @@
+use crate::parsing::{paragraphs, value};
 use crate::{bail, eyre, Aoc, Day13, FileRep, Result};
 use std::fmt::Display;
+use tracing::instrument;
 
 #[derive(Eq, PartialEq, Clone)]
 enum List<T> {
@@ -64,12 +66,15 @@ impl Aoc for Day13 {
     }
 }
 
+#[instrument(level = "debug", skip_all)]
 fn parse(lines: &[&[u8]]) -> Result<Vec<(List<u8>, List<u8>)>> {
-    lines
-        .split(|&l| l.is_empty())
+    paragraphs(lines)
         .map(|ll| {
-            let [l0,l1] = ll[..] else {
-                bail!("parse error");
+            let [l0, l1] = ll[..] else {
+                bail!(
+                    "parse error: expected a pair of lists, got {} lines",
+                    ll.len()
+                );
             };
             Ok((parse_line(l0)?, parse_line(l1)?))
         })
@@ -93,7 +98,7 @@ fn parse_line(line: &[u8]) -> Result<List<u8>> {
             while j < line.len() && b'0' <= line[j] && line[j] <= b'9' {
                 j += 1;
             }
-            let x = std::str::from_utf8(&line[i..j])?.parse::<u8>()?;
+            let x = value::<u8, _>(&line[i..j])?;
             let p = stack.last_mut().ok_or_else(|| eyre!("parse error"))?;
             p.push(List::Val(x));
             i = j - 1;
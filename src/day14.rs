@@ -1,5 +1,8 @@
-use crate::{bail, Aoc, Day14, FileRep, Result};
+use crate::parsing::scan_as;
+use crate::visualize::{self, Frame};
+use crate::{Aoc, Day14, FileRep, Result};
 use std::fmt::Display;
+use tracing::debug;
 
 // dummy input:
 // 498,4 -> 498,6 -> 496,6
@@ -37,10 +40,7 @@ impl Aoc for Day14 {
             unroll_path(&path).for_each(|(x, y)| grid[x][y] = Rock)
         }
 
-        let res =
-            std::iter::repeat_with(|| insert_sand(&grid, max_y).map(|(x, y)| grid[x][y] = Sand))
-                .take_while(|x| x.is_some())
-                .count();
+        let res = pour_sand(&mut grid, max_y, "day 14 part 1")?;
 
         result!(res)
     }
@@ -72,24 +72,46 @@ impl Aoc for Day14 {
             unroll_path(&path).for_each(|(x, y)| grid[x][y] = Rock)
         }
 
-        let res =
-            std::iter::repeat_with(|| insert_sand(&grid, max_y).map(|(x, y)| grid[x][y] = Sand))
-                .take_while(|x| x.is_some())
-                .count();
+        let res = pour_sand(&mut grid, max_y, "day 14 part 2")?;
 
         result!(res)
     }
 }
 
+// drops sand from column x until no more comes to rest, returning the
+// number of units that did
+fn pour_sand(grid: &mut [Vec<Square>], x: usize, title: &str) -> Result<usize> {
+    let mut res = 0;
+    while let Some((x, y)) = insert_sand(grid, x) {
+        grid[x][y] = Sand;
+        res += 1;
+        visualize::emit(|| render(grid, format!("{title}: {res} units of sand")))?;
+    }
+    debug!(units = res, "sand at rest");
+    Ok(res)
+}
+
+fn render(grid: &[Vec<Square>], title: String) -> Frame {
+    let height = grid.first().map_or(0, |col| col.len());
+    let rows = (0..height).map(|y| {
+        grid.iter()
+            .map(|col| match col[y] {
+                Empty => b'.',
+                Rock => b'#',
+                Sand => b'o',
+            })
+            .collect::<Vec<u8>>()
+    });
+    Frame::new(title, rows)
+}
+
 fn insert_sand(grid: &[Vec<Square>], x: usize) -> Option<(usize, usize)> {
     let (mut x, mut y) = (x, 0);
     if grid[x][y] != Empty {
         return None;
     }
     loop {
-        let Some(&s) = grid[x].get(y+1) else {
-            return None;
-        };
+        let &s = grid[x].get(y + 1)?;
 
         if s == Empty {
         } else if grid[x - 1][y + 1] == Empty {
@@ -108,10 +130,8 @@ fn parse_line(line: &[u8]) -> Result<Vec<(usize, usize)>> {
     std::str::from_utf8(line)?
         .split(" -> ")
         .map(|w| {
-            let [x,y] = w.splitn(2, ',').collect::<Vec<_>>()[..] else {
-                bail!("parse error");
-            };
-            Ok((x.parse::<usize>()?, y.parse::<usize>()?))
+            let [x, y] = scan_as(w, "{},{}")?;
+            Ok((x, y))
         })
         .collect()
 }
@@ -119,7 +139,7 @@ fn parse_line(line: &[u8]) -> Result<Vec<(usize, usize)>> {
 // fn unroll_path(path: &[(usize,usize)]) -> Vec<(usize,usize)> {
 fn unroll_path(path: &[(usize, usize)]) -> impl Iterator<Item = (usize, usize)> + '_ {
     path.windows(2).flat_map(|w| {
-        let [(x1,y1),(x2,y2)] = w[..] else {
+        let [(x1, y1), (x2, y2)] = w[..] else {
             panic!();
         };
         let (x1, x2) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
@@ -1,4 +1,5 @@
-use crate::{bail, eyre, Aoc, Day15, FileRep, Result};
+use crate::parsing::{parse_lines, scan_as};
+use crate::{bail, Aoc, Day15, FileRep, Result};
 use std::collections::BTreeSet as Set;
 use std::fmt::Display;
 
@@ -12,10 +13,7 @@ use std::fmt::Display;
 impl Aoc for Day15 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.string_lines;
-        let data: Vec<[(i32, i32); 2]> = lines
-            .iter()
-            .map(|line| parse_line(line))
-            .collect::<Result<_>>()?;
+        let data: Vec<[(i32, i32); 2]> = parse_lines(lines, |line| parse_line(line))?;
 
         let f10 = forbidden(10, &data);
         let f2000000 = forbidden(2000000, &data);
@@ -25,10 +23,7 @@ impl Aoc for Day15 {
 
     fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.string_lines;
-        let data: Vec<[(i32, i32); 2]> = lines
-            .iter()
-            .map(|line| parse_line(line))
-            .collect::<Result<_>>()?;
+        let data: Vec<[(i32, i32); 2]> = parse_lines(lines, |line| parse_line(line))?;
 
         if let Some(res) = search_beacon::<4000000>(&data) {
             return result!(res);
@@ -39,15 +34,11 @@ impl Aoc for Day15 {
 }
 
 fn parse_line(line: &str) -> Result<[(i32, i32); 2]> {
-    fn parse_var(s: &str) -> Result<i32, std::num::ParseIntError> {
-        s[2..].parse::<i32>()
-    }
-    fn parse_pair(s: &str) -> Result<(i32, i32)> {
-        let (x, y) = s.split_once(", ").ok_or_else(|| eyre!("parse error"))?;
-        Ok((parse_var(x)?, parse_var(y)?))
-    }
-    let (l, r) = line.split_once(": ").ok_or_else(|| eyre!("parse error"))?;
-    Ok([parse_pair(&l[10..])?, parse_pair(&r[21..])?])
+    let [sx, sy, bx, by] = scan_as(
+        line,
+        "Sensor at x={}, y={}: closest beacon is at x={}, y={}",
+    )?;
+    Ok([(sx, sy), (bx, by)])
 }
 
 fn manhattan(p1: (i32, i32), p2: (i32, i32)) -> i32 {
@@ -55,8 +46,7 @@ fn manhattan(p1: (i32, i32), p2: (i32, i32)) -> i32 {
 }
 
 fn ball_cut(row: i32, (sx, sy): (i32, i32), r: i32) -> Option<(i32, i32)> {
-    r.checked_sub((sy - row).abs())
-        .map(|rx| (sx - rx as i32, sx + rx as i32))
+    r.checked_sub((sy - row).abs()).map(|rx| (sx - rx, sx + rx))
 }
 
 // assumes a sorted, non-empty input
@@ -126,7 +116,7 @@ fn forbidden(y: i32, data: &[[(i32, i32); 2]]) -> usize {
 }
 
 fn search_beacon<const MULT: i32>(data: &[[(i32, i32); 2]]) -> Option<usize> {
-    for y in 0..=MULT as i32 {
+    for y in 0..=MULT {
         let mut segments = vec![];
 
         for &[s, b] in data {
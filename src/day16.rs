@@ -1,6 +1,9 @@
-use crate::{bail, eyre, Aoc, Day16, FileRep, Result};
+use crate::bitset::BitSet64;
+use crate::parsing::{parse_lines, scan, value};
+use crate::{bail, Aoc, Day16, FileRep, Result};
 use std::collections::BinaryHeap as Heap;
 use std::fmt::Display;
+use tracing::{debug, trace};
 
 // dummy input:
 //
@@ -12,11 +15,8 @@ use std::fmt::Display;
 impl Aoc for Day16 {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.string_lines;
-        let mut data = lines
-            .into_iter()
-            .map(|l| parse_line(l))
-            .collect::<Result<Vec<_>>>()?;
-        data.sort_unstable_by(|x, y| x.0.cmp(&y.0));
+        let mut data = parse_lines(lines, |l| parse_line(l))?;
+        data.sort_unstable_by_key(|x| x.0);
 
         let mut valves = vec![];
         let mut rates: Vec<usize> = vec![];
@@ -72,11 +72,8 @@ impl Aoc for Day16 {
 
     fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>> {
         let lines = &input.string_lines;
-        let mut data = lines
-            .into_iter()
-            .map(|l| parse_line(l))
-            .collect::<Result<Vec<_>>>()?;
-        data.sort_unstable_by(|x, y| x.0.cmp(&y.0));
+        let mut data = parse_lines(lines, |l| parse_line(l))?;
+        data.sort_unstable_by_key(|x| x.0);
 
         let mut valves = vec![];
         let mut rates: Vec<usize> = vec![];
@@ -127,47 +124,51 @@ impl Aoc for Day16 {
 
         let mut res = 0;
 
-        for mut partition in 0..2_usize.pow(positive_valves.len() as u32 - 1) {
-            let mut my_valves = vec![0];
-            let mut el_valves = vec![0];
+        // subsets are indices into positive_valves; the last valve always
+        // goes to the elephant so that each partition is only seen once
+        let all = BitSet64::full(positive_valves.len());
+        let last = BitSet64::singleton(positive_valves.len() - 1);
 
-            for &valve in &positive_valves {
-                if partition & 1 == 1 {
-                    my_valves.push(valve);
-                } else {
-                    el_valves.push(valve);
-                }
-                partition >>= 1;
-            }
+        for mine_set in (all - last).subsets() {
+            let valves = |s: BitSet64| {
+                [0].into_iter()
+                    .chain(s.iter().map(|i| positive_valves[i]))
+                    .collect::<Vec<_>>()
+            };
+            let my_valves = valves(mine_set);
+            let el_valves = valves(all - mine_set);
 
             let mine = best_permutation(26, &distances, &gr.weights, &my_valves);
             let elephant = best_permutation(26, &distances, &gr.weights, &el_valves);
             let total = mine.0 + elephant.0;
+            trace!(?mine_set, total, "partition");
             if res < total {
                 res = total;
             }
         }
 
+        debug!(partitions = 1 << (positive_valves.len() - 1), best = res);
+
         result!(res)
     }
 }
 
 fn parse_line(line: &str) -> Result<(u16, (usize, Vec<u16>))> {
-    fn valve(s: &str) -> u16 {
-        let res = u16::from_ne_bytes(s.as_bytes()[0..2].try_into().unwrap());
-        res
+    fn valve(s: &str) -> Result<u16> {
+        let &[a, b] = s.as_bytes() else {
+            bail!("parse error: invalid valve name {s:?}");
+        };
+        Ok(u16::from_ne_bytes([a, b]))
     }
 
-    let (l, r) = line
-        .split_once("; tunnels lead to valves ")
-        .or_else(|| line.split_once("; tunnel leads to valve "))
-        .ok_or_else(|| eyre!("parse error"))?;
-    let (ll, lr) = &l[6..]
-        .split_once(" has flow rate=")
-        .ok_or_else(|| eyre!("parse error"))?;
-    let r: Vec<u16> = r.split(", ").map(|v| valve(v)).collect();
+    let [name, rate, tunnels] = scan(line, "Valve {} has flow rate={}; tunnels lead to valves {}")
+        .or_else(|_| scan(line, "Valve {} has flow rate={}; tunnel leads to valve {}"))?;
+    let tunnels = tunnels
+        .split(", ")
+        .map(valve)
+        .collect::<Result<Vec<u16>>>()?;
 
-    Ok((valve(ll), (lr.parse()?, r)))
+    Ok((valve(name)?, (value(rate)?, tunnels)))
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
@@ -226,7 +227,7 @@ fn distance_clique(gr: &Graph, targets: &[usize]) -> Vec<Arc<usize>> {
             vert: v, cost: c, ..
         }) = to_process.pop()
         {
-            if targets.iter().any(|&t| t == v) {
+            if targets.contains(&v) {
                 paths.push(visited[v].clone());
             }
 
@@ -257,6 +258,8 @@ fn distance_clique(gr: &Graph, targets: &[usize]) -> Vec<Arc<usize>> {
         }
     }
 
+    debug!(arcs = res.len(), "distance clique");
+
     res
 }
 
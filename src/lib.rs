@@ -1,5 +1,6 @@
 use color_eyre::eyre::*;
 use std::fmt::Display;
+use tracing::{info, info_span, warn};
 // use std::time::Duration;
 
 macro_rules! result {
@@ -25,16 +26,86 @@ mod day14;
 mod day15;
 mod day16;
 
-pub struct Day01;
-pub struct Day02;
-pub struct Day03;
-pub struct Day04;
-pub struct Day05;
-pub struct Day06;
-pub struct Day07;
-pub struct Day08;
-pub struct Day09;
-pub struct Day10;
+pub mod bitset;
+pub mod ocr;
+pub mod parsing;
+pub mod visualize;
+
+pub use day02::Game;
+pub use day06::Alphabet;
+
+pub struct Day01 {
+    /// Number of elves whose calories are summed in part 2.
+    pub top: usize,
+    /// Print statistics about the elves' inventories.
+    pub report: bool,
+}
+pub struct Day02 {
+    pub game: Game,
+    /// Total score to find a strategy for, given the opponent's moves.
+    pub target: Option<u32>,
+}
+pub struct Day03 {
+    /// Number of equal parts each rucksack is split into in part 1.
+    pub compartments: usize,
+    /// Number of rucksacks sharing a badge in part 2.
+    pub group_size: usize,
+    /// Search for badge groups among rucksacks taken in any order.
+    pub report: bool,
+}
+pub struct Day04 {
+    /// Count the pairs of assignments in each interval relation.
+    pub report: bool,
+    /// Analyse how all assignments cover the sections, listing those
+    /// covered by more than this many elves.
+    pub coverage: Option<usize>,
+}
+pub struct Day05 {
+    /// Draw the stacks after `step` moves, and check that undoing the moves
+    /// brings back the starting drawing.
+    pub report: bool,
+    /// Number of moves after which the stacks are drawn, by default all.
+    pub step: Option<usize>,
+    /// Also run the moves with a crane lifting at most this many crates at
+    /// a time.
+    pub capacity: Option<usize>,
+}
+pub struct Day06 {
+    /// List every position where a marker of this many symbols ends.
+    pub marker: Option<usize>,
+    pub alphabet: Alphabet,
+}
+pub struct Day07 {
+    /// Total space on the device.
+    pub disk_size: usize,
+    /// Free space the update needs.
+    pub needed: usize,
+    /// Query on the rebuilt filesystem: `tree [PATH]`, `du [PATH]` or
+    /// `find [PATH] PREDICATE...` with predicates `name=PATTERN`,
+    /// `type=d|f`, `size>N`, `size<N` and `size=N`.
+    pub query: Option<String>,
+    /// Recreate the filesystem in a new directory under this one, with
+    /// sparse files.
+    pub export: Option<std::path::PathBuf>,
+}
+pub struct Day08 {
+    /// Directions trees look in, as steps of `(row, column)`.
+    pub directions: Vec<(isize, isize)>,
+    /// Trees see over trees at least this much shorter than themselves.
+    pub see_over: usize,
+    /// Farthest distance a tree sees, in steps, for scenic scores.
+    pub max_distance: Option<usize>,
+}
+pub struct Day09 {
+    /// Number of knots of the rope in part 2.
+    pub knots: usize,
+    /// Count the cells visited by every knot of the part 2 rope.
+    pub report: bool,
+}
+pub struct Day10 {
+    /// Print the screen as it is drawn, without reading its letters.
+    pub report: bool,
+}
 pub struct Day11;
 pub struct Day12;
 pub struct Day13;
@@ -43,7 +114,9 @@ pub struct Day15;
 pub struct Day16;
 
 pub struct FileRep<'a> {
+    #[allow(dead_code)]
     bytes: Vec<u8>,
+    #[allow(dead_code)]
     string: String,
     byte_lines: Vec<&'a [u8]>,
     string_lines: Vec<&'a str>,
@@ -52,8 +125,13 @@ pub struct FileRep<'a> {
 pub trait Aoc {
     fn part1(&self, input: &FileRep) -> Result<Box<dyn Display>>;
     fn part2(&self, input: &FileRep) -> Result<Box<dyn Display>>;
+    /// Extra output shown after both answers, e.g. a rendering of the solution.
+    fn report(&self, _input: &FileRep) -> Result<Option<Box<dyn Display>>> {
+        Ok(None)
+    }
     fn run<P: AsRef<std::path::Path> + Copy>(&self, fpath: P) -> Result<()> {
         // fn run<P: AsRef<std::path::Path> + Copy>(&self, rep: &FileRep) -> Result<()> {
+        let _span = info_span!("input", path = %fpath.as_ref().display()).entered();
         let bytes = std::fs::read(fpath)?;
         let byte_lines = byte_lines(&bytes);
         let bytes = std::fs::read(fpath)?;
@@ -69,14 +147,23 @@ pub trait Aoc {
         };
 
         let t0 = std::time::SystemTime::now();
-        let res1 = self.part1(&rep);
+        let res1 = info_span!("part", n = 1).in_scope(|| self.part1(&rep));
         let t1 = t0.elapsed();
         let t0 = std::time::SystemTime::now();
-        let res2 = self.part2(&rep);
+        let res2 = info_span!("part", n = 2).in_scope(|| self.part2(&rep));
         let t2 = t0.elapsed();
 
-        println!("part 1: {} ({:?})", res1?, t1?);
-        println!("part 2: {} ({:?})", res2?, t2?);
+        for (part, res, t) in [(1, res1, t1), (2, res2, t2)] {
+            let res = res
+                .inspect_err(|e| warn!(part, error = %format!("{e:#}"), "part failed"))
+                .wrap_err_with(|| format!("part {part}"))?;
+            let t = t?;
+            info!(part, answer = %res, elapsed = ?t, "part done");
+            println!("part {part}: {} ({:?})", res, t);
+        }
+        if let Some(report) = self.report(&rep).wrap_err("report")? {
+            println!("report: {report}");
+        }
 
         Ok(())
     }
@@ -84,7 +171,7 @@ pub trait Aoc {
 
 fn byte_lines(input: &[u8]) -> Vec<&[u8]> {
     input
-        .strip_suffix(&[b'\n'])
+        .strip_suffix(b"\n")
         .unwrap_or(input)
         .split(|b| *b == b'\n')
         .collect()
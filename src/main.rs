@@ -1,38 +1,307 @@
 use color_eyre::eyre::Result;
 
-use aoc2022::Aoc;
+use aoc2022::visualize::{self, FileFormat};
+use aoc2022::{Alphabet, Aoc, Game};
 
-// use clap::{Parser, ValueEnum};
+use clap::{Parser, ValueEnum};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+struct Args {
+    /// Only run the given day
+    #[arg(long)]
+    day: Option<u8>,
+
+    /// Show the simulating days (5, 9, 10, 14) as they run, and day 8 heatmaps
+    #[arg(long, value_enum)]
+    visualize: Option<Backend>,
+
+    /// Frames per second of the terminal animation
+    #[arg(long, default_value_t = 30.0, value_parser = positive)]
+    fps: f64,
+
+    /// Only keep every n-th frame in the terminal animation
+    #[arg(long, default_value_t = 1)]
+    every: usize,
+
+    /// Directory receiving frame files
+    #[arg(long, default_value = "frames")]
+    frames_dir: std::path::PathBuf,
+
+    /// Pixels per cell in image frames
+    #[arg(long, default_value_t = 4)]
+    scale: usize,
+
+    /// Number of elves whose calories are summed in day 1 part 2
+    #[arg(long, default_value_t = 3)]
+    top: usize,
+
+    /// Number of compartments per rucksack in day 3
+    #[arg(long, default_value_t = 2)]
+    compartments: usize,
+
+    /// Number of elves per group in day 3
+    #[arg(long, default_value_t = 3)]
+    group_size: usize,
+
+    /// Analyse day 4 section coverage, listing sections covered by more than K elves
+    #[arg(long, value_name = "K")]
+    coverage: Option<usize>,
+
+    /// Also run day 5 with a crane lifting at most N crates at a time
+    #[arg(long, value_name = "N")]
+    capacity: Option<usize>,
+
+    /// Draw the day 5 stacks after this many moves (with --report)
+    #[arg(long, value_name = "N")]
+    step: Option<usize>,
+
+    /// List every day 6 marker of this many bytes
+    #[arg(long, value_name = "LEN")]
+    marker: Option<usize>,
+
+    /// Read the day 6 signal as UTF-8 characters rather than bytes
+    #[arg(long)]
+    utf8: bool,
+
+    /// Only allow these characters in the day 6 signal
+    #[arg(long, value_name = "CHARS")]
+    alphabet: Option<String>,
+
+    /// Total disk space in day 7
+    #[arg(long, default_value_t = 70000000)]
+    disk_size: usize,
+
+    /// Free space needed by the day 7 update
+    #[arg(long, default_value_t = 30000000)]
+    needed: usize,
+
+    /// Query the day 7 filesystem: "tree [PATH]", "du [PATH]" or
+    /// "find [PATH] name=PATTERN type=d|f size>N size<N size=N"
+    #[arg(long, value_name = "QUERY")]
+    fs: Option<String>,
+
+    /// Recreate the day 7 filesystem with sparse files in a new directory under DIR
+    #[arg(long, value_name = "DIR")]
+    fs_export: Option<std::path::PathBuf>,
+
+    /// Print a day 7 transcript listing DIR and exit
+    #[arg(long, value_name = "DIR")]
+    fs_snapshot: Option<std::path::PathBuf>,
+
+    /// Day 8 sight lines: 4, 8, or steps "di,dj" separated by spaces
+    #[arg(long, default_value = "4", allow_hyphen_values = true)]
+    directions: String,
+
+    /// Day 8 trees see over trees at least this much shorter
+    #[arg(long, default_value_t = 1)]
+    see_over: usize,
+
+    /// Farthest distance day 8 trees see for scenic scores
+    #[arg(long, value_name = "STEPS")]
+    max_distance: Option<usize>,
+
+    /// Number of knots of the day 9 rope in part 2
+    #[arg(long, default_value_t = 10)]
+    knots: usize,
+
+    /// Find day 2 moves reaching this total score against the opponent
+    #[arg(long, value_name = "SCORE")]
+    target: Option<u32>,
+
+    /// Play day 2 as Rock Paper Scissors Lizard Spock, our moves being V to Z
+    #[arg(long)]
+    lizard_spock: bool,
+
+    /// Print the extra reports that some days can produce
+    #[arg(long)]
+    report: bool,
+
+    /// Log solver internals to stderr, filtered by RUST_LOG (default: debug)
+    #[arg(long)]
+    trace: bool,
+
+    /// Write the trace as JSON lines to this file instead of stderr
+    #[arg(long, value_name = "PATH")]
+    trace_file: Option<std::path::PathBuf>,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Backend {
+    Terminal,
+    Text,
+    Ppm,
+}
 
 macro_rules! run_day {
-    ($day: ident) => {
+    ($args: expr, $day: ident) => {
+        run_day!($args, $day, aoc2022::$day);
+    };
+    ($args: expr, $day: ident, $solver: expr) => {
         let s = stringify!($day).to_ascii_lowercase();
 
-        println!("=== {s} ===");
-        println!("dummy input:");
-        aoc2022::$day.run(&format!("input/{s}.dummy.txt"))?;
-        println!("personal input:");
-        aoc2022::$day.run(&format!("input/{s}.txt"))?;
+        if $args.day.map_or(true, |d| format!("day{d:02}") == s) {
+            let _span = tracing::info_span!("day", day = %s).entered();
+            println!("=== {s} ===");
+            println!("dummy input:");
+            $solver.run(&format!("input/{s}.dummy.txt"))?;
+            println!("personal input:");
+            $solver.run(&format!("input/{s}.txt"))?;
+        }
     };
 }
 
+fn positive(s: &str) -> std::result::Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(x) if x > 0.0 => Ok(x),
+        Ok(_) => Err("must be positive".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 fn main() -> Result<()> {
-    run_day!(Day01);
-    run_day!(Day02);
-    run_day!(Day03);
-    run_day!(Day04);
-    run_day!(Day05);
-    run_day!(Day06);
-    run_day!(Day07);
-    run_day!(Day08);
-    run_day!(Day09);
-    run_day!(Day10);
-    run_day!(Day11);
-    run_day!(Day12);
-    run_day!(Day13);
-    run_day!(Day14);
-    // run_day!(Day15);
-    run_day!(Day16);
+    let args = Args::parse();
+
+    if args.trace || args.trace_file.is_some() {
+        let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("debug"));
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_span_events(FmtSpan::CLOSE);
+        match &args.trace_file {
+            Some(path) => {
+                let file = std::fs::File::create(path)?;
+                subscriber
+                    .json()
+                    .with_writer(std::sync::Mutex::new(file))
+                    .init();
+            }
+            None => subscriber.with_writer(std::io::stderr).init(),
+        }
+    }
+
+    if let Some(dir) = &args.fs_snapshot {
+        print!("{}", aoc2022::Day07::transcript_from_dir(dir)?);
+        return Ok(());
+    }
+
+    match args.visualize {
+        Some(Backend::Terminal) => {
+            visualize::install(visualize::Terminal::new(args.fps, args.every))
+        }
+        Some(Backend::Text) => visualize::install(visualize::Files::new(
+            &args.frames_dir,
+            FileFormat::Text,
+            args.scale,
+        )?),
+        Some(Backend::Ppm) => visualize::install(visualize::Files::new(
+            &args.frames_dir,
+            FileFormat::Ppm,
+            args.scale,
+        )?),
+        None => {}
+    }
+
+    run_day!(
+        args,
+        Day01,
+        aoc2022::Day01 {
+            top: args.top,
+            report: args.report,
+        }
+    );
+    run_day!(
+        args,
+        Day02,
+        aoc2022::Day02 {
+            game: if args.lizard_spock {
+                Game::rock_paper_scissors_lizard_spock()
+            } else {
+                Game::rock_paper_scissors()
+            },
+            target: args.target,
+        }
+    );
+    run_day!(
+        args,
+        Day03,
+        aoc2022::Day03 {
+            compartments: args.compartments,
+            group_size: args.group_size,
+            report: args.report,
+        }
+    );
+    run_day!(
+        args,
+        Day04,
+        aoc2022::Day04 {
+            report: args.report,
+            coverage: args.coverage,
+        }
+    );
+    run_day!(
+        args,
+        Day05,
+        aoc2022::Day05 {
+            report: args.report,
+            step: args.step,
+            capacity: args.capacity,
+        }
+    );
+    run_day!(
+        args,
+        Day06,
+        aoc2022::Day06 {
+            marker: args.marker,
+            alphabet: match &args.alphabet {
+                Some(chars) => Alphabet::Only(chars.chars().collect()),
+                None if args.utf8 => Alphabet::Chars,
+                None => Alphabet::Bytes,
+            },
+        }
+    );
+    run_day!(
+        args,
+        Day07,
+        aoc2022::Day07 {
+            disk_size: args.disk_size,
+            needed: args.needed,
+            query: args.fs.clone(),
+            export: args.fs_export.clone(),
+        }
+    );
+    run_day!(
+        args,
+        Day08,
+        aoc2022::Day08 {
+            directions: aoc2022::Day08::directions(&args.directions)?,
+            see_over: args.see_over,
+            max_distance: args.max_distance,
+        }
+    );
+    run_day!(
+        args,
+        Day09,
+        aoc2022::Day09 {
+            knots: args.knots,
+            report: args.report,
+        }
+    );
+    run_day!(
+        args,
+        Day10,
+        aoc2022::Day10 {
+            report: args.report,
+        }
+    );
+    run_day!(args, Day11);
+    run_day!(args, Day12);
+    run_day!(args, Day13);
+    run_day!(args, Day14);
+    // run_day!(args, Day15);
+    run_day!(args, Day16);
+
+    visualize::finish()?;
 
     Ok(())
 }
@@ -0,0 +1,242 @@
+use crate::{bail, Result};
+
+// Recognizes the block letters AoC uses for some answers, e.g. the CRT of
+// 2022 day 10. Lit pixels are `#`, anything else is blank.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Font {
+    /// 4x6 letters, one blank column apart.
+    Small,
+    /// 6x10 letters, two blank columns apart.
+    Large,
+}
+
+impl Font {
+    fn height(self) -> usize {
+        match self {
+            Font::Small => 6,
+            Font::Large => 10,
+        }
+    }
+
+    fn stride(self) -> usize {
+        match self {
+            Font::Small => 5,
+            Font::Large => 8,
+        }
+    }
+
+    fn glyphs(self) -> &'static [(char, &'static str)] {
+        match self {
+            Font::Small => SMALL,
+            Font::Large => LARGE,
+        }
+    }
+
+    /// Picks the font from the number of rows in a rendering.
+    pub fn for_height(height: usize) -> Option<Font> {
+        [Font::Small, Font::Large]
+            .into_iter()
+            .find(|f| f.height() == height)
+    }
+}
+
+/// Decodes a rendering given as rows, guessing the font from its height.
+pub fn recognize<S: AsRef<[u8]>>(rows: &[S]) -> Result<String> {
+    let Some(font) = Font::for_height(rows.len()) else {
+        bail!("ocr: no font is {} pixels high", rows.len());
+    };
+    recognize_with(font, rows)
+}
+
+pub fn recognize_with<S: AsRef<[u8]>>(font: Font, rows: &[S]) -> Result<String> {
+    let rows: Vec<&[u8]> = rows.iter().map(|r| r.as_ref()).collect();
+    if rows.len() != font.height() {
+        bail!(
+            "ocr: {font:?} font is {} pixels high, got {} rows",
+            font.height(),
+            rows.len()
+        );
+    }
+
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let mut res = String::new();
+
+    for col in (0..width).step_by(font.stride()) {
+        let cell: Vec<&[u8]> = rows
+            .iter()
+            .map(|r| &r[col.min(r.len())..(col + font.stride()).min(r.len())])
+            .collect();
+        let glyph = normalize(&cell);
+        if glyph.is_empty() {
+            continue;
+        }
+        let Some(&(c, _)) = font
+            .glyphs()
+            .iter()
+            .find(|(_, g)| normalize(&g.lines().map(str::as_bytes).collect::<Vec<_>>()) == glyph)
+        else {
+            bail!("ocr: unrecognized glyph at column {col}:\n{glyph}");
+        };
+        res.push(c);
+    }
+
+    Ok(res)
+}
+
+// renders a cell as `#`/`.` rows without its blank side columns, so that
+// glyphs compare equal whatever their offset inside the cell
+fn normalize(rows: &[&[u8]]) -> String {
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let lit = |col: usize| rows.iter().any(|r| r.get(col) == Some(&b'#'));
+    let Some(left) = (0..width).find(|&c| lit(c)) else {
+        return String::new();
+    };
+    let right = (0..width).rev().find(|&c| lit(c)).unwrap_or(left);
+
+    rows.iter()
+        .map(|r| {
+            (left..=right)
+                .map(|c| if r.get(c) == Some(&b'#') { '#' } else { '.' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const SMALL: &[(char, &str)] = &[
+    ('A', ".##.\n#..#\n#..#\n####\n#..#\n#..#"),
+    ('B', "###.\n#..#\n###.\n#..#\n#..#\n###."),
+    ('C', ".##.\n#..#\n#...\n#...\n#..#\n.##."),
+    ('E', "####\n#...\n###.\n#...\n#...\n####"),
+    ('F', "####\n#...\n###.\n#...\n#...\n#..."),
+    ('G', ".##.\n#..#\n#...\n#.##\n#..#\n.###"),
+    ('H', "#..#\n#..#\n####\n#..#\n#..#\n#..#"),
+    ('I', ".###\n..#.\n..#.\n..#.\n..#.\n.###"),
+    ('J', "..##\n...#\n...#\n...#\n#..#\n.##."),
+    ('K', "#..#\n#.#.\n##..\n#.#.\n#.#.\n#..#"),
+    ('L', "#...\n#...\n#...\n#...\n#...\n####"),
+    ('O', ".##.\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('P', "###.\n#..#\n#..#\n###.\n#...\n#..."),
+    ('R', "###.\n#..#\n#..#\n###.\n#.#.\n#..#"),
+    ('S', ".###\n#...\n#...\n.##.\n...#\n###."),
+    ('U', "#..#\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('Y', "#...#\n#...#\n.#.#.\n..#..\n..#..\n..#.."),
+    ('Z', "####\n...#\n..#.\n.#..\n#...\n####"),
+];
+
+const LARGE: &[(char, &str)] = &[
+    (
+        'A',
+        "..##..\n.#..#.\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#",
+    ),
+    (
+        'B',
+        "#####.\n#....#\n#....#\n#....#\n#####.\n#....#\n#....#\n#....#\n#....#\n#####.",
+    ),
+    (
+        'C',
+        ".####.\n#....#\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#....#\n.####.",
+    ),
+    (
+        'E',
+        "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n######",
+    ),
+    (
+        'F',
+        "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n#.....",
+    ),
+    (
+        'G',
+        ".####.\n#....#\n#.....\n#.....\n#.....\n#..###\n#....#\n#....#\n#...##\n.###.#",
+    ),
+    (
+        'H',
+        "#....#\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#\n#....#",
+    ),
+    (
+        'J',
+        "...###\n....#.\n....#.\n....#.\n....#.\n....#.\n....#.\n#...#.\n#...#.\n.###..",
+    ),
+    (
+        'K',
+        "#....#\n#...#.\n#..#..\n#.#...\n##....\n##....\n#.#...\n#..#..\n#...#.\n#....#",
+    ),
+    (
+        'L',
+        "#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n######",
+    ),
+    (
+        'N',
+        "#....#\n##...#\n##...#\n#.#..#\n#.#..#\n#..#.#\n#..#.#\n#...##\n#...##\n#....#",
+    ),
+    (
+        'P',
+        "#####.\n#....#\n#....#\n#....#\n#####.\n#.....\n#.....\n#.....\n#.....\n#.....",
+    ),
+    (
+        'R',
+        "#####.\n#....#\n#....#\n#....#\n#####.\n#..#..\n#...#.\n#...#.\n#....#\n#....#",
+    ),
+    (
+        'X',
+        "#....#\n#....#\n.#..#.\n.#..#.\n..##..\n..##..\n.#..#.\n.#..#.\n#....#\n#....#",
+    ),
+    (
+        'Z',
+        "######\n.....#\n.....#\n....#.\n...#..\n..#...\n.#....\n#.....\n#.....\n######",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_font() {
+        let rows = [
+            "####.#.....##..###..",
+            "#....#....#..#.#..#.",
+            "###..#....#..#.#..#.",
+            "#....#....#..#.###..",
+            "#....#....#..#.#....",
+            "#....####..##..#....",
+        ];
+        assert_eq!(recognize(&rows).unwrap(), "FLOP");
+    }
+
+    #[test]
+    fn large_font() {
+        let rows = [
+            "#....#..#....#..######..",
+            "#....#..##...#.......#..",
+            "#....#..##...#.......#..",
+            "#....#..#.#..#......#...",
+            "######..#.#..#.....#....",
+            "#....#..#..#.#....#.....",
+            "#....#..#..#.#...#......",
+            "#....#..#...##..#.......",
+            "#....#..#...##..#.......",
+            "#....#..#....#..######..",
+        ];
+        assert_eq!(recognize(&rows).unwrap(), "HNZ");
+    }
+
+    #[test]
+    fn unrecognized_glyph() {
+        let rows = [
+            "####.####.",
+            "#..#.#..#.",
+            "#..#.#..#.",
+            "#..#.#..#.",
+            "#..#.#..#.",
+            "####.####.",
+        ];
+        let err = recognize(&rows).unwrap_err();
+        assert!(
+            err.to_string().contains("unrecognized glyph at column 0"),
+            "{err}"
+        );
+        assert!(recognize(&["#"; 7]).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{bail, eyre, Result, WrapErr};
+use std::any::type_name;
+use std::fmt::Debug;
+use std::str::FromStr;
+use tracing::debug_span;
+
+// Small helpers shared by the day parsers. Every function accepts either
+// `&str` or `&[u8]`, since days use both `string_lines` and `byte_lines`.
+
+/// Parses a whole field, e.g. a number, reporting the offending text on error.
+pub fn value<T, S>(s: &S) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Debug,
+    S: AsRef<[u8]> + ?Sized,
+{
+    let s = as_str(s.as_ref())?;
+    s.parse().map_err(|e| {
+        eyre!(
+            "parse error: {s:?} is not a valid {}: {e:?}",
+            type_name::<T>()
+        )
+    })
+}
+
+/// Extracts every integer in `s`, skipping any other characters. A `-`
+/// directly in front of digits is read as a sign.
+pub fn ints<T, S>(s: &S) -> Result<Vec<T>>
+where
+    T: FromStr,
+    T::Err: Debug,
+    S: AsRef<[u8]> + ?Sized,
+{
+    let bytes = s.as_ref();
+    let mut res = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        if bytes[i] == b'-' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit) {
+            i += 1;
+        }
+        if !bytes[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        res.push(value(&bytes[start..i])?);
+    }
+
+    Ok(res)
+}
+
+/// Matches `line` against `template`, where each `{}` captures the shortest
+/// text up to the next literal part (or the rest of the line for a trailing
+/// `{}`), e.g. `scan("x=3, y=-4", "x={}, y={}")` yields `["3", "-4"]`.
+/// Fields must be separated by some literal text.
+pub fn scan<'a, S, const N: usize>(line: &'a S, template: &str) -> Result<[&'a str; N]>
+where
+    S: AsRef<[u8]> + ?Sized,
+{
+    let line = as_str(line.as_ref())?;
+    let mut parts = template.split("{}");
+    let nfields = parts.clone().count() - 1;
+    if nfields != N {
+        bail!("template {template:?} has {nfields} fields, expected {N}");
+    }
+    if template.contains("{}{}") {
+        bail!("template {template:?} has adjacent fields");
+    }
+
+    let first = parts.next().unwrap_or_default();
+    let mut rest = line
+        .strip_prefix(first)
+        .ok_or_else(|| mismatch(line, template, 0, first))?;
+    let mut res = [""; N];
+
+    for (i, literal) in parts.enumerate() {
+        let col = line.len() - rest.len();
+        let end = if literal.is_empty() && i + 1 == N {
+            rest.len()
+        } else {
+            rest.find(literal)
+                .ok_or_else(|| mismatch(line, template, col, literal))?
+        };
+        res[i] = &rest[..end];
+        rest = &rest[end + literal.len()..];
+    }
+
+    if !rest.is_empty() {
+        bail!(
+            "parse error: trailing {rest:?} at column {} of {line:?} (template {template:?})",
+            line.len() - rest.len() + 1
+        );
+    }
+
+    Ok(res)
+}
+
+/// Like [`scan`], but also parses every captured field as a `T`.
+pub fn scan_as<T, S, const N: usize>(line: &S, template: &str) -> Result<[T; N]>
+where
+    T: FromStr,
+    T::Err: Debug,
+    S: AsRef<[u8]> + ?Sized,
+{
+    let fields: [&str; N] = scan(line, template)?;
+    let values = fields.into_iter().map(value).collect::<Result<Vec<T>>>()?;
+    values
+        .try_into()
+        .map_err(|_| eyre!("unreachable: wrong number of fields"))
+}
+
+/// Splits `lines` into the blocks separated by empty lines.
+pub fn paragraphs<S>(lines: &[S]) -> impl Iterator<Item = &[S]>
+where
+    S: AsRef<[u8]>,
+{
+    lines.split(|l| l.as_ref().is_empty())
+}
+
+/// Applies `f` to each line, tagging errors with the 1-based line number.
+pub fn parse_lines<'a, S, T, F>(lines: &'a [S], f: F) -> Result<Vec<T>>
+where
+    F: FnMut(&'a S) -> Result<T>,
+{
+    parse_lines_from(1, lines, f)
+}
+
+/// Like [`parse_lines`], for a block of lines starting at line `first` of
+/// the input.
+pub fn parse_lines_from<'a, S, T, F>(first: usize, lines: &'a [S], mut f: F) -> Result<Vec<T>>
+where
+    F: FnMut(&'a S) -> Result<T>,
+{
+    let _span = debug_span!("parse_lines", lines = lines.len()).entered();
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, l)| f(l).wrap_err_with(|| format!("on line {}", first + i)))
+        .collect()
+}
+
+fn as_str(bytes: &[u8]) -> Result<&str> {
+    std::str::from_utf8(bytes).wrap_err("parse error: invalid utf-8")
+}
+
+fn mismatch(line: &str, template: &str, col: usize, expected: &str) -> crate::Report {
+    eyre!(
+        "parse error: expected {expected:?} at column {} of {line:?} (template {template:?})",
+        col + 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_fields() {
+        let [x, y] = scan("x=3, y=-4", "x={}, y={}").unwrap();
+        assert_eq!((x, y), ("3", "-4"));
+        let [a, b] = scan(b"move 1 to 2".as_slice(), "move {} to {}").unwrap();
+        assert_eq!((a, b), ("1", "2"));
+        // a field followed by a literal stops at its first occurrence
+        let [a, b] = scan("a-b-c", "{}-{}").unwrap();
+        assert_eq!((a, b), ("a", "b-c"));
+        let [empty] = scan("x=", "x={}").unwrap();
+        assert_eq!(empty, "");
+    }
+
+    #[test]
+    fn scan_errors() {
+        let err = scan::<_, 1>("y=3", "x={}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"parse error: expected "x=" at column 1 of "y=3" (template "x={}")"#
+        );
+        let err = scan::<_, 2>("1 2", "{},{}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"parse error: expected "," at column 1 of "1 2" (template "{},{}")"#
+        );
+        let err = scan::<_, 1>("x=1; y", "x={};").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"parse error: trailing " y" at column 5 of "x=1; y" (template "x={};")"#
+        );
+        let err = scan::<_, 1>("1 2", "{} {}").unwrap_err();
+        assert!(
+            err.to_string().contains("has 2 fields, expected 1"),
+            "{err}"
+        );
+        let err = scan::<_, 2>("12", "{}{}").unwrap_err();
+        assert!(err.to_string().contains("adjacent fields"), "{err}");
+        assert!(scan::<_, 0>(b"\xff".as_slice(), "").is_err());
+    }
+
+    #[test]
+    fn scan_as_values() {
+        let [x, y]: [i32; 2] = scan_as("x=-3, y=+4", "x={}, y={}").unwrap();
+        assert_eq!((x, y), (-3, 4));
+        let err = scan_as::<u8, _, 1>("n=-1", "n={}").unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with(r#"parse error: "-1" is not a valid u8"#),
+            "{err}"
+        );
+        let err = scan_as::<u32, _, 1>("n=12", "n={}!").unwrap_err();
+        assert!(err.to_string().contains(r#"expected "!""#), "{err}");
+    }
+
+    #[test]
+    fn ints_with_signs() {
+        let xs: Vec<i64> = ints("Sensor at x=-2, y=15: -, 3-4").unwrap();
+        assert_eq!(xs, [-2, 15, 3, -4]);
+        assert_eq!(ints::<i32, _>(b"".as_slice()).unwrap(), Vec::<i32>::new());
+        let err = ints::<u8, _>("1, 300").unwrap_err();
+        assert!(
+            err.to_string().contains(r#""300" is not a valid u8"#),
+            "{err}"
+        );
+        let err = ints::<u32, _>("x=-1").unwrap_err();
+        assert!(
+            err.to_string().contains(r#""-1" is not a valid u32"#),
+            "{err}"
+        );
+    }
+}
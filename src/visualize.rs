@@ -0,0 +1,216 @@
+use crate::{Result, WrapErr};
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Solvers describe their state as character grids and hand them to
+// `emit`; whichever backend was installed by the runner turns them into an
+// animation or a sequence of files. Nothing is built when none is installed.
+
+#[derive(Clone, Debug, Default)]
+pub struct Frame {
+    pub title: String,
+    pub rows: Vec<Vec<u8>>,
+    /// Colour of each cell, overriding the palette of `color` where given.
+    pub colors: Vec<Vec<[u8; 3]>>,
+}
+
+impl Frame {
+    pub fn new<T, R, I>(title: T, rows: I) -> Frame
+    where
+        T: Into<String>,
+        R: Into<Vec<u8>>,
+        I: IntoIterator<Item = R>,
+    {
+        Frame {
+            title: title.into(),
+            rows: rows.into_iter().map(Into::into).collect(),
+            colors: vec![],
+        }
+    }
+
+    /// Paints the cells with explicit colours, given row by row.
+    pub fn with_colors(self, colors: Vec<Vec<[u8; 3]>>) -> Frame {
+        Frame { colors, ..self }
+    }
+
+    pub fn color_at(&self, row: usize, col: usize) -> [u8; 3] {
+        match self.colors.get(row).and_then(|r| r.get(col)) {
+            Some(&c) => c,
+            None => color(self.rows[row].get(col).copied().unwrap_or(b' ')),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows.iter().map(|r| r.len()).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in &self.rows {
+            writeln!(f, "{}", String::from_utf8_lossy(row))?;
+        }
+        Ok(())
+    }
+}
+
+pub trait Visualize {
+    fn frame(&mut self, frame: &Frame) -> Result<()>;
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+static SINK: Mutex<Option<Box<dyn Visualize + Send>>> = Mutex::new(None);
+
+/// Sends every frame emitted from now on to `backend`.
+pub fn install(backend: impl Visualize + Send + 'static) {
+    *SINK.lock().unwrap() = Some(Box::new(backend));
+}
+
+pub fn enabled() -> bool {
+    SINK.lock().unwrap().is_some()
+}
+
+/// Builds a frame with `f` and passes it to the installed backend, if any.
+pub fn emit(f: impl FnOnce() -> Frame) -> Result<()> {
+    match SINK.lock().unwrap().as_mut() {
+        Some(backend) => backend.frame(&f()),
+        None => Ok(()),
+    }
+}
+
+/// Flushes and removes the installed backend.
+pub fn finish() -> Result<()> {
+    match SINK.lock().unwrap().take() {
+        Some(mut backend) => backend.finish(),
+        None => Ok(()),
+    }
+}
+
+/// Colour used for a cell by the image and terminal backends.
+pub fn color(cell: u8) -> [u8; 3] {
+    match cell {
+        b' ' | b'.' => [16, 16, 24],
+        b'#' => [200, 200, 200],
+        b'o' | b'~' => [230, 190, 80],
+        b'H' | b'T' => [220, 60, 60],
+        b'0'..=b'9' => [220, 120, 60],
+        b'A'..=b'Z' | b'[' | b']' => [170, 120, 70],
+        _ => [90, 160, 220],
+    }
+}
+
+/// Animates frames in place on a terminal using ANSI escapes.
+pub struct Terminal {
+    /// Pause after each displayed frame.
+    pub delay: Duration,
+    /// Only every `every`-th frame is displayed.
+    pub every: usize,
+    count: usize,
+}
+
+impl Terminal {
+    /// Panics unless `fps` is positive.
+    pub fn new(fps: f64, every: usize) -> Terminal {
+        Terminal {
+            delay: Duration::from_secs_f64(1.0 / fps),
+            every: every.max(1),
+            count: 0,
+        }
+    }
+}
+
+impl Visualize for Terminal {
+    fn frame(&mut self, frame: &Frame) -> Result<()> {
+        self.count += 1;
+        if !(self.count - 1).is_multiple_of(self.every) {
+            return Ok(());
+        }
+
+        let mut out = String::from("\x1b[H\x1b[2J");
+        out.push_str(&frame.title);
+        out.push('\n');
+        for (i, row) in frame.rows.iter().enumerate() {
+            for (j, &c) in row.iter().enumerate() {
+                let [r, g, b] = frame.color_at(i, j);
+                out.push_str(&format!("\x1b[38;2;{r};{g};{b}m{}", c as char));
+            }
+            out.push_str("\x1b[0m\n");
+        }
+
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(out.as_bytes())?;
+        stdout.flush()?;
+        std::thread::sleep(self.delay);
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    Text,
+    Ppm,
+}
+
+/// Writes each frame to its own numbered file in `dir`.
+pub struct Files {
+    pub dir: PathBuf,
+    pub format: FileFormat,
+    /// Side of the square of pixels drawn for each cell in images.
+    pub scale: usize,
+    count: usize,
+}
+
+impl Files {
+    pub fn new(dir: impl Into<PathBuf>, format: FileFormat, scale: usize) -> Result<Files> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .wrap_err_with(|| format!("cannot create {}", dir.display()))?;
+        Ok(Files {
+            dir,
+            format,
+            scale: scale.max(1),
+            count: 0,
+        })
+    }
+
+    fn ppm(&self, frame: &Frame) -> Vec<u8> {
+        let (width, height) = (frame.width() * self.scale, frame.height() * self.scale);
+        let mut res = format!("P6\n{width} {height}\n255\n").into_bytes();
+        for row in 0..frame.height() {
+            let mut line = Vec::with_capacity(3 * width);
+            for col in 0..frame.width() {
+                let px = frame.color_at(row, col);
+                for _ in 0..self.scale {
+                    line.extend_from_slice(&px);
+                }
+            }
+            for _ in 0..self.scale {
+                res.extend_from_slice(&line);
+            }
+        }
+        res
+    }
+}
+
+impl Visualize for Files {
+    fn frame(&mut self, frame: &Frame) -> Result<()> {
+        let (ext, contents) = match self.format {
+            FileFormat::Text => ("txt", format!("{}\n{frame}", frame.title).into_bytes()),
+            FileFormat::Ppm => ("ppm", self.ppm(frame)),
+        };
+        let path = self.dir.join(format!("frame{:06}.{ext}", self.count));
+        std::fs::write(&path, contents)
+            .wrap_err_with(|| format!("cannot write {}", path.display()))?;
+        self.count += 1;
+        Ok(())
+    }
+}